@@ -1,51 +1,158 @@
+use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::iter::Enumerate;
 use std::vec::Vec;
+use utils::topk::TopK;
 
 fn main() {
-    part1();
-    part2();
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+
+    println!(
+        "{}",
+        part1(&contents).expect("Should have been able to count the calories")
+    );
+    println!(
+        "{}",
+        part2(&contents, 3).expect("Should have been able to count the calories")
+    );
 }
 
-fn part1() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+/// A line that is neither blank nor a number of calories. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CaloriesError {
+    line: usize,
+    text: String,
+}
+
+impl fmt::Display for CaloriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}: {:?} is not a number of calories",
+            self.line, self.text
+        )
+    }
+}
+
+impl Error for CaloriesError {}
 
-    let mut max = 0;
-    let mut current = 0;
-    for line in contents.split("\n") {
-        if line.len() == 0 {
-            if current > max {
-                max = current
+/// The sums of the blocks of numbers separated by blank lines, as the lines are read. The last
+/// block does not need a blank line after it, and several blank lines in a row do not make
+/// empty blocks.
+struct BlockSums<'a, I: Iterator<Item = &'a str>> {
+    lines: Enumerate<I>,
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Iterator for BlockSums<'a, I> {
+    type Item = Result<u64, CaloriesError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sum: Option<u64> = None;
+        for (idx, line) in self.lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() {
+                if sum.is_some() {
+                    break;
+                }
+                continue;
+            }
+            match line.parse::<u64>() {
+                Ok(calories) => sum = Some(sum.unwrap_or(0) + calories),
+                Err(_) => {
+                    return Some(Err(CaloriesError {
+                        line: idx + 1,
+                        text: line.to_string(),
+                    }))
+                }
             }
-            current = 0;
-            continue;
         }
-        current += line.parse::<i32>().unwrap();
+        sum.map(Ok)
     }
+}
 
-    println!("{max}")
+fn block_sums<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> BlockSums<'a, I::IntoIter> {
+    BlockSums {
+        lines: lines.into_iter().enumerate(),
+    }
 }
 
-fn part2() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+/// The calories carried by the `k` elves carrying the most, biggest first.
+fn top_calories(contents: &str, k: usize) -> Result<Vec<u64>, CaloriesError> {
+    let mut top = TopK::new(k);
+    for sum in block_sums(contents.lines()) {
+        top.push(sum?);
+    }
+    Ok(top.into_sorted_vec())
+}
 
-    let mut calories = Vec::new();
-    let mut current = 0;
-    for line in contents.split("\n") {
-        if line.len() == 0 {
-            calories.push(current);
-            current = 0;
-            continue;
-        }
-        current += line.parse::<i32>().unwrap();
+fn part1(contents: &String) -> Result<u64, CaloriesError> {
+    Ok(top_calories(contents, 1)?.iter().sum())
+}
+
+/// The calories carried by the `k` elves carrying the most.
+fn part2(contents: &String, k: usize) -> Result<u64, CaloriesError> {
+    Ok(top_calories(contents, k)?.iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "1000
+2000
+3000
+
+4000
+
+5000
+6000
+
+7000
+8000
+9000
+
+10000";
+
+    #[test]
+    fn test_parts() {
+        let contents = EXAMPLE.to_string();
+        assert_eq!(part1(&contents), Ok(24000));
+        assert_eq!(part2(&contents, 3), Ok(45000));
+        assert_eq!(part2(&contents, 5), Ok(55000));
+        assert_eq!(part2(&contents, 10), Ok(55000));
+        assert_eq!(part2(&contents, 0), Ok(0));
     }
 
-    calories.sort();
-    calories.reverse();
+    #[test]
+    fn test_block_sums() {
+        let sums: Vec<u64> = block_sums(EXAMPLE.lines()).map(Result::unwrap).collect();
+        assert_eq!(sums, [6000, 4000, 11000, 24000, 10000]);
 
-    let mut tot = 0;
-    for i in [0, 1, 2] {
-        tot += calories[i]
+        // the last block counts, with or without blank lines after it
+        assert_eq!(part1(&"1\n\n5".to_string()), Ok(5));
+        assert_eq!(part1(&"1\n\n5\n\n\n".to_string()), Ok(5));
+        // blank lines in a row or before the first block do not make elves
+        let sums: Vec<u64> = block_sums("\n\n1\n2\n\n\n\n3\n".lines())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sums, [3, 3]);
+        assert_eq!(block_sums("".lines()).count(), 0);
+        assert_eq!(part1(&"".to_string()), Ok(0));
     }
 
-    println!("{tot}")
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            part2(&"1\n\n2\nthree\n".to_string(), 3),
+            Err(CaloriesError {
+                line: 4,
+                text: "three".to_string()
+            })
+        );
+        assert_eq!(
+            part1(&"-1".to_string()).unwrap_err().to_string(),
+            "Line 1: \"-1\" is not a number of calories"
+        );
+    }
 }
@@ -1,110 +1,80 @@
-use std::collections::HashMap;
+use std::error::Error;
 use std::fs;
-use std::str::FromStr;
 use std::vec::Vec;
+use utils::ocr;
+use utils::vm::{InstructionSet, Jump, Machine, ParseError};
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    part1(&contents);
-    part2(&contents);
+    part1(&contents).expect("Could not run the program");
+    part2(&contents).expect("Could not read the CRT");
 }
 
-struct CPU {
-    X: Vec<i32>,
-    cycle: u32,
-}
-
-impl CPU {
-    fn run(&mut self, instr: &str) {
-        let tokens: Vec<&str> = instr.split(" ").collect();
-        match tokens[0] {
-            "noop" => self.noop(),
-            "addx" => {
-                let v = tokens[1].parse::<i32>().unwrap();
-                self.addx(v);
-            }
-            _ => (),
-        }
-    }
+const X: usize = 0;
 
-    fn noop(&mut self) {
-        self.cycle += 1;
-        self.X.push(*self.X.last().unwrap());
-    }
+fn load(program: &str) -> Result<Machine, ParseError> {
+    let mut cpu = InstructionSet::new(&["x"])
+        .with("noop", 0, 1, |_, _| Jump::Next)
+        .with("addx", 1, 2, |regs, ops| {
+            regs[X] += regs.read(&ops[0]);
+            Jump::Next
+        })
+        .load(program)?;
+    cpu.registers[X] = 1;
+    Ok(cpu)
+}
 
-    fn addx(&mut self, value: i32) {
-        self.cycle += 2;
-        self.X.push(*self.X.last().unwrap());
-        self.X.push(*self.X.last().unwrap() + value);
-    }
+fn part1(contents: &String) -> Result<i64, ParseError> {
+    let mut cpu = load(contents)?;
 
-    fn value_at_cycle(&self, cycle: usize) -> i32 {
-        let v: i32;
-        if cycle >= self.X.len() {
-            v = *self.X.last().unwrap();
-        } else {
-            v = self.X[cycle - 1];
+    let mut sum = 0;
+    cpu.run(|cycle, regs| {
+        if cycle % 40 == 20 && cycle <= 220 {
+            let signal = cycle as i64 * regs[X];
+            println!("Signal strength at cycle {}: {}", cycle, signal);
+            sum += signal;
         }
-        v
-    }
+    });
 
-    fn signal_at_cycle(&self, cycle: usize) -> i32 {
-        self.value_at_cycle(cycle) * (cycle as i32)
-    }
+    println!("Sum {}", sum);
+    Ok(sum)
 }
 
-fn part1(contents: &String) {
-    let mut cpu = CPU {
-        X: [1].to_vec(),
-        cycle: 0,
-    };
+fn part2(contents: &String) -> Result<String, Box<dyn Error>> {
+    let mut cpu = load(contents)?;
 
-    for line in contents.lines() {
-        cpu.run(line);
+    let screen = crt_screen(&mut cpu);
+    for row in screen.iter() {
+        println!("{}", row);
     }
 
-    let mut sum = 0;
-    for cycle in [20, 60, 100, 140, 180, 220] {
-        let signal = cpu.signal_at_cycle(cycle as usize);
-        println!("Signal strength at cycle {}: {}", cycle, signal);
-        sum += signal;
+    let letters = ocr::recognize_text(&screen.join("\n"));
+    match &letters {
+        Ok(letters) => println!("Letters: {}", letters),
+        Err(e) => println!("{}", e),
     }
-
-    println!("Sum {}", sum);
+    Ok(letters?)
 }
 
-fn part2(contents: &String) {
-    let mut cpu = CPU {
-        X: [1].to_vec(),
-        cycle: 0,
-    };
-
-    for line in contents.lines() {
-        cpu.run(line);
-    }
-
-    let mut pixels: Vec<char> = Vec::new();
-    for cycle in 1i32..=240 {
-        let x = cpu.value_at_cycle(cycle as usize);
-        if x + 1 >= (cycle - 1) % 40 && x - 1 <= (cycle - 1) % 40 {
-            pixels.push('#');
-        } else {
-            pixels.push('.');
+/// Runs the program, drawing a pixel during each cycle where the sprite covers the beam.
+fn crt_screen(cpu: &mut Machine) -> Vec<String> {
+    let mut pixels = vec!['.'; 240];
+    cpu.run(|cycle, regs| {
+        let beam = (cycle - 1) as i64;
+        if beam < 240 && (regs[X] - beam % 40).abs() <= 1 {
+            pixels[beam as usize] = '#';
         }
-    }
+    });
 
-    println!("{:?}", String::from_iter(&pixels[0..40]));
-    println!("{:?}", String::from_iter(&pixels[40..80]));
-    println!("{:?}", String::from_iter(&pixels[80..120]));
-    println!("{:?}", String::from_iter(&pixels[120..160]));
-    println!("{:?}", String::from_iter(&pixels[160..200]));
-    println!("{:?}", String::from_iter(&pixels[200..240]));
+    pixels.chunks(40).map(String::from_iter).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+    use utils::ocr::OcrError;
 
     #[test]
     fn test_parts() {
@@ -258,7 +228,41 @@ noop",
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(13140));
+
+        assert_eq!(
+            crt_screen(&mut load(&test_contents).unwrap()),
+            [
+                "##..##..##..##..##..##..##..##..##..##..",
+                "###...###...###...###...###...###...###.",
+                "####....####....####....####....####....",
+                "#####.....#####.....#####.....#####.....",
+                "######......######......######......####",
+                "#######.......#######.......#######.....",
+            ]
+        );
+
+        // the example does not draw letters, and has no blank column to split glyphs
+        match part2(&test_contents).map_err(|e| e.downcast::<OcrError>()) {
+            Err(Ok(e)) => match *e {
+                OcrError::UnknownGlyphs { partial, unknown } => {
+                    assert_eq!(partial, "?");
+                    assert_eq!(unknown[0].column, 0);
+                }
+                e => panic!("Unexpected OCR error {:?}", e),
+            },
+            res => panic!("Unexpected OCR result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        assert_eq!(
+            part1(&"noop\nmulx 3\nnoop".to_string()),
+            Err(ParseError::UnknownOpcode {
+                line: 2,
+                opcode: "mulx".to_string()
+            })
+        );
     }
 }
@@ -1,163 +1,512 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
-use std::fs;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 use std::str::FromStr;
 use std::vec::Vec;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// The operation of a monkey, compiled once from e.g. `old * (old + 3)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Old,
+    Const(u64),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+impl Expr {
+    /// Computes the new worry level. With a modulus, every intermediate result is reduced by it,
+    /// which keeps the result right modulo `modulus` as long as there is no division. Without
+    /// one, worry levels cannot go below 0. Dividing by 0 and going over `u64::MAX` are errors.
+    fn eval(&self, old: u64, modulus: Option<u64>) -> Result<u64, String> {
+        let reduce = |v: u128| match modulus {
+            Some(m) => Ok((v % m as u128) as u64),
+            None => u64::try_from(v).map_err(|_| format!("{} overflows when old is {}", self, old)),
+        };
+        match self {
+            Expr::Old => reduce(old as u128),
+            Expr::Const(c) => reduce(*c as u128),
+            Expr::Binary(lhs, op, rhs) => {
+                let l = lhs.eval(old, modulus)? as u128;
+                let r = rhs.eval(old, modulus)? as u128;
+                match (op, modulus) {
+                    (Op::Add, _) => reduce(l + r),
+                    (Op::Mul, _) => reduce(l * r),
+                    (Op::Sub, Some(m)) => reduce(l + m as u128 - r),
+                    (Op::Sub, None) => reduce(l.saturating_sub(r)),
+                    (Op::Div, _) => reduce(l.checked_div(r).ok_or_else(|| {
+                        format!("{} is 0 when old is {}, cannot divide by it", rhs, old)
+                    })?),
+                }
+            }
+        }
+    }
+
+    fn has_division(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Const(_) => false,
+            Expr::Binary(l, op, r) => *op == Op::Div || l.has_division() || r.has_division(),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Const(c) => write!(f, "{}", c),
+            Expr::Binary(l, op, r) => {
+                let symbol = match op {
+                    Op::Add => '+',
+                    Op::Sub => '-',
+                    Op::Mul => '*',
+                    Op::Div => '/',
+                };
+                write!(f, "({} {} {})", l, symbol, r)
+            }
+        }
+    }
+}
+
+/// Recursive-descent parser, with the usual precedence:
+/// `expr = term {("+" | "-") term}`, `term = factor {("*" | "/") factor}`,
+/// `factor = "old" | number | "(" expr ")"`.
+struct ExprParser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn tokenize(s: &'a str) -> Vec<&'a str> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (idx, c) in s.char_indices() {
+            if c.is_ascii_alphanumeric() {
+                start.get_or_insert(idx);
+                continue;
+            }
+            if let Some(start) = start.take() {
+                tokens.push(&s[start..idx]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[idx..idx + c.len_utf8()]);
+            }
+        }
+        if let Some(start) = start {
+            tokens.push(&s[start..]);
+        }
+        tokens
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn binary<F>(&mut self, ops: &[(&str, Op)], operand: F) -> Result<Expr, String>
+    where
+        F: Fn(&mut Self) -> Result<Expr, String>,
+    {
+        let mut expr = operand(self)?;
+        while let Some((_, op)) = self
+            .tokens
+            .get(self.position)
+            .and_then(|token| ops.iter().find(|(symbol, _)| symbol == token))
+        {
+            self.position += 1;
+            let rhs = operand(self)?;
+            if *op == Op::Div && rhs == Expr::Const(0) {
+                return Err("Division by 0".to_string());
+            }
+            expr = Expr::Binary(Box::new(expr), *op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[("+", Op::Add), ("-", Op::Sub)], Self::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        self.binary(&[("*", Op::Mul), ("/", Op::Div)], Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some("old") => Ok(Expr::Old),
+            Some("(") => {
+                let expr = self.expr()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    other => Err(format!("Expected ')', found {:?}", other)),
+                }
+            }
+            Some(token) => token
+                .parse()
+                .map(Expr::Const)
+                .map_err(|_| format!("Unexpected {}", token)),
+            None => Err("Unexpected end of the operation".to_string()),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser {
+            tokens: ExprParser::tokenize(s),
+            position: 0,
+        };
+        let expr = parser.expr()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {} after the operation", token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Monkey {
-    items: RefCell<VecDeque<u64>>,
-    op_symbol: String,
-    op_operand: String,
+    items: Vec<u64>,
+    operation: Expr,
     test_div: u64,
-    if_true: u32,
-    if_false: u32,
-    items_inspected: RefCell<u64>,
+    if_true: usize,
+    if_false: usize,
 }
 
 impl Monkey {
-    fn apply_op(&self, item: u64) -> u64 {
-        match self.op_symbol.as_str() {
-            "+" => match self.op_operand.as_str() {
-                "old" => item * 2,
-                a => item + a.parse::<u64>().unwrap(),
-            },
-            "*" => match self.op_operand.as_str() {
-                "old" => item * item,
-                a => item * a.parse::<u64>().unwrap(),
-            },
-            _ => item,
+    fn test(&self, item: u64) -> bool {
+        item.is_multiple_of(self.test_div)
+    }
+
+    fn throw_to(&self, divisible: bool) -> usize {
+        if divisible {
+            self.if_true
+        } else {
+            self.if_false
         }
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+/// How worry levels are kept in check after each inspection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relief {
+    /// Divided, as in part 1
+    Divide(u64),
+    /// Reduced modulo a multiple of all the divisibility tests, as in part 2
+    Modulo(u64),
+}
 
-    part1(&contents);
-    part2(&contents);
+impl Relief {
+    /// The smallest modulus that keeps the tests of all the monkeys right.
+    fn modulo_for(monkeys: &[Monkey]) -> Result<Self, String> {
+        let gcd = |mut a: u64, mut b: u64| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        monkeys
+            .iter()
+            .try_fold(1u64, |lcm, m| {
+                (lcm / gcd(lcm, m.test_div)).checked_mul(m.test_div)
+            })
+            .map(Relief::Modulo)
+            .ok_or_else(|| "The divisibility tests have no common multiple below 2^64".to_string())
+    }
+}
+
+/// One inspection of an item by a monkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Step {
+    /// Starting at 1
+    round: usize,
+    item: usize,
+    monkey: usize,
+    /// Worry level once the item is inspected and relief applied
+    worry: u64,
+    divisible: bool,
+    thrown_to: usize,
 }
 
-fn part1(contents: &String) {
-    let mut monkeys = parse_monkeys(contents);
+/// Where the items went during a simulation. Items are numbered in the order of the input,
+/// starting at 0.
+#[derive(Debug, Clone, Default)]
+struct Trace {
+    // the monkey holding each item at the start
+    start: Vec<usize>,
+    steps: Vec<Step>,
+}
 
-    for round in 0..20 {
-        println!("Round {}", round + 1);
-        run_round(&monkeys, 3, std::u64::MAX);
-        // dbg!(&monkeys);
-        // println!("$$$$$$$$$$$$\n");
+impl Trace {
+    /// The inspections of `item`, in order.
+    fn path(&self, item: usize) -> impl Iterator<Item = &Step> + '_ {
+        self.steps.iter().filter(move |step| step.item == item)
     }
 
-    // dbg!(&monkeys);
+    /// The monkey holding `item` at the end of `round`, or at the start for round 0.
+    fn holder(&self, item: usize, round: usize) -> Option<usize> {
+        let start = *self.start.get(item)?;
+        Some(
+            self.path(item)
+                .take_while(|step| step.round <= round)
+                .last()
+                .map_or(start, |step| step.thrown_to),
+        )
+    }
 
-    monkeys.sort_by_key(|m| *m.items_inspected.borrow());
-    monkeys.reverse();
+    fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "round,item,monkey,worry,divisible,thrown_to")?;
+        for step in self.steps.iter() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                step.round, step.item, step.monkey, step.worry, step.divisible, step.thrown_to
+            )?;
+        }
+        Ok(())
+    }
+}
 
-    println!(
-        "Monkey business level: {}",
-        *monkeys[0].items_inspected.borrow() * *monkeys[1].items_inspected.borrow()
-    );
+/// Runs monkeys for any number of rounds, counting the items each one inspects.
+struct Simulation {
+    monkeys: Vec<Monkey>,
+    // (item number, worry level) held by each monkey
+    items: Vec<VecDeque<(usize, u64)>>,
+    inspections: Vec<u64>,
+    relief: Relief,
+    rounds: usize,
+    trace: Option<Trace>,
 }
 
-fn part2(contents: &String) {
-    let mut monkeys = parse_monkeys(contents);
+impl Simulation {
+    fn new(monkeys: &[Monkey], relief: Relief) -> Result<Self, String> {
+        if let Relief::Modulo(_) = relief {
+            if let Some(idx) = monkeys.iter().position(|m| m.operation.has_division()) {
+                return Err(format!(
+                    "Monkey {} divides, which cannot be done modulo a number",
+                    idx
+                ));
+            }
+        }
+        let mut ids = 0..;
+        Ok(Simulation {
+            monkeys: monkeys.to_vec(),
+            items: monkeys
+                .iter()
+                .map(|m| m.items.iter().map(|w| (ids.next().unwrap(), *w)).collect())
+                .collect(),
+            inspections: vec![0; monkeys.len()],
+            relief,
+            rounds: 0,
+            trace: None,
+        })
+    }
+
+    /// Records every inspection from now on, see `trace`.
+    fn traced(mut self) -> Self {
+        let mut start = Vec::new();
+        for (monkey, items) in self.items.iter().enumerate() {
+            for (item, _) in items.iter() {
+                if *item >= start.len() {
+                    start.resize(item + 1, 0);
+                }
+                start[*item] = monkey;
+            }
+        }
+        self.trace = Some(Trace {
+            start,
+            steps: Vec::new(),
+        });
+        self
+    }
+
+    fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    fn round(&mut self) -> Result<(), String> {
+        for idx in 0..self.monkeys.len() {
+            let monkey = &self.monkeys[idx];
+            // a monkey never throws to itself, so its items can be taken out
+            let items = std::mem::take(&mut self.items[idx]);
+            self.inspections[idx] += items.len() as u64;
+            for (item, worry) in items {
+                let worry = match self.relief {
+                    Relief::Divide(d) => monkey.operation.eval(worry, None).map(|w| w / d),
+                    Relief::Modulo(m) => monkey.operation.eval(worry, Some(m)),
+                }
+                .map_err(|e| format!("Monkey {}: {}", idx, e))?;
+                let divisible = monkey.test(worry);
+                let target = monkey.throw_to(divisible);
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.steps.push(Step {
+                        round: self.rounds + 1,
+                        item,
+                        monkey: idx,
+                        worry,
+                        divisible,
+                        thrown_to: target,
+                    });
+                }
+                self.items[target].push_back((item, worry));
+            }
+        }
+        self.rounds += 1;
+        Ok(())
+    }
+
+    fn run(&mut self, rounds: usize) -> Result<(), String> {
+        for _ in 0..rounds {
+            self.round()?;
+        }
+        Ok(())
+    }
 
-    let mut mod_factor: u64 = 1;
-    for monkey in monkeys.iter() {
-        mod_factor = mod_factor * monkey.test_div;
+    fn inspections(&self) -> &[u64] {
+        &self.inspections
     }
 
-    for round in 0..10000 {
-        println!("Round {}", round + 1);
-        run_round(&monkeys, 1, mod_factor);
-        // dbg!(&monkeys);
-        // println!("$$$$$$$$$$$$\n");
+    /// Product of the inspection counts of the two most active monkeys.
+    fn monkey_business(&self) -> u64 {
+        let mut inspections = self.inspections.clone();
+        inspections.sort_unstable_by(|a, b| b.cmp(a));
+        inspections.iter().take(2).product()
     }
+}
 
-    // dbg!(&monkeys);
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    for monkey in monkeys.iter() {
-        println!("items checked: {}", *monkey.items_inspected.borrow());
+    // `--trace trace.csv` exports where the items go in part 2, and `--where 3:500` tells where
+    // item 3 was after round 500
+    let arg = |name: &str| env::args().skip_while(|arg| arg != name).nth(1);
+    let query = arg("--where").map(|q| {
+        let (item, round) = q.split_once(':').expect("Expected --where item:round");
+        (
+            item.parse().expect("Bad item number"),
+            round.parse().expect("Bad round number"),
+        )
+    });
+
+    part1(&contents);
+    part2(&contents, arg("--trace").as_deref(), query);
+}
+
+fn part1(contents: &String) -> u64 {
+    let monkeys = parse_monkeys(contents).expect("Could not read the monkeys");
+    let mut simulation = Simulation::new(&monkeys, Relief::Divide(3)).unwrap();
+    simulation.run(20).expect("The monkeys could not play");
+
+    let monkey_business = simulation.monkey_business();
+    println!("Monkey business level: {}", monkey_business);
+    monkey_business
+}
+
+fn part2(contents: &String, trace: Option<&str>, query: Option<(usize, usize)>) -> u64 {
+    let monkeys = parse_monkeys(contents).expect("Could not read the monkeys");
+    let relief = Relief::modulo_for(&monkeys).expect("Cannot reduce worry levels");
+    let mut simulation = Simulation::new(&monkeys, relief).expect("Cannot reduce worry levels");
+    if trace.is_some() || query.is_some() {
+        simulation = simulation.traced();
     }
+    simulation.run(10000).expect("The monkeys could not play");
 
-    monkeys.sort_by_key(|m| *m.items_inspected.borrow());
-    monkeys.reverse();
+    if let (Some(path), Some(steps)) = (trace, simulation.trace()) {
+        let file = BufWriter::new(File::create(path).expect("Cannot create the trace file"));
+        steps.write_csv(file).expect("Cannot write the trace");
+        println!("Trace written to {}", path);
+    }
+    if let (Some((item, round)), Some(steps)) = (query, simulation.trace()) {
+        match steps.holder(item, round) {
+            Some(monkey) => println!(
+                "Item {} is held by monkey {} after round {}",
+                item, monkey, round
+            ),
+            None => println!("There is no item {}", item),
+        }
+        for step in steps.path(item).filter(|step| step.round == round) {
+            println!("  {:?}", step);
+        }
+    }
+
+    for count in simulation.inspections() {
+        println!("items checked: {}", count);
+    }
 
-    let monkey_business: u64 =
-        *monkeys[0].items_inspected.borrow() * *monkeys[1].items_inspected.borrow();
+    let monkey_business = simulation.monkey_business();
     println!("Monkey business level: {}", monkey_business);
+    monkey_business
 }
 
-fn parse_monkeys(contents: &String) -> Vec<Monkey> {
+fn parse_monkeys(contents: &str) -> Result<Vec<Monkey>, String> {
     let mut monkeys: Vec<Monkey> = Vec::new();
 
-    let mut lines = contents.lines();
-    loop {
-        let _monkey_line = lines.next().unwrap();
-        let starting = lines.next().unwrap();
-        let operation = lines.next().unwrap();
-        let test_str = lines.next().unwrap();
-        let test_true = lines.next().unwrap();
-        let test_false = lines.next().unwrap();
-        let empty = lines.next();
-
-        let items_str: VecDeque<&str> = starting[18..].split(", ").collect();
-        let ops: Vec<&str> = operation[23..].split(" ").collect();
-        let test_div: u64 = test_str[21..].parse::<u64>().unwrap();
-        let if_true: u32 = test_true[29..].parse::<u32>().unwrap();
-        let if_false: u32 = test_false[30..].parse::<u32>().unwrap();
-
-        let mut items: VecDeque<u64> = VecDeque::new();
-        for i in items_str.iter() {
-            items.push_back(i.parse::<u64>().unwrap());
-        }
+    for (idx, block) in contents.split("\n\n").enumerate() {
+        let mut lines = block.lines().map(str::trim);
+        let mut field = |prefix: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(prefix))
+                .ok_or(format!("Monkey {}: expected '{}'", idx, prefix))
+        };
+        let number = |s: &str| {
+            s.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("Monkey {}: {} is not a number", idx, s))
+        };
+
+        field(&format!("Monkey {}:", idx))?;
+        let items = field("Starting items:")?
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(number)
+            .collect::<Result<Vec<u64>, String>>()?;
+        let operation = field("Operation: new =")?
+            .parse::<Expr>()
+            .map_err(|e| format!("Monkey {}: {}", idx, e))?;
+        let test_div = number(field("Test: divisible by")?)?;
+        let if_true = number(field("If true: throw to monkey")?)? as usize;
+        let if_false = number(field("If false: throw to monkey")?)? as usize;
+
         monkeys.push(Monkey {
-            items: RefCell::new(items),
-            op_symbol: String::from(ops[0]),
-            op_operand: String::from(ops[1]),
+            items,
+            operation,
             test_div,
             if_true,
             if_false,
-            items_inspected: RefCell::new(0),
         });
-
-        // dbg!(monkeys.last().unwrap());
-
-        if empty.is_none() {
-            break;
-        }
     }
 
-    monkeys
-}
-
-fn run_round(monkeys: &Vec<Monkey>, worry_reducer: u64, mod_factor: u64) {
-    for monkey in monkeys {
-        while monkey.items.borrow().len() > 0 {
-            let mut item = monkey.items.borrow_mut().pop_front().unwrap();
-            *monkey.items_inspected.borrow_mut() += 1;
-            item = monkey.apply_op(item) % mod_factor;
-            item = item / worry_reducer;
-            let next_monkey: &Monkey;
-            if item % monkey.test_div == 0 {
-                next_monkey = &monkeys[monkey.if_true as usize];
-            } else {
-                next_monkey = &monkeys[monkey.if_false as usize];
+    for (idx, monkey) in monkeys.iter().enumerate() {
+        for target in [monkey.if_true, monkey.if_false] {
+            if target >= monkeys.len() || target == idx {
+                return Err(format!("Monkey {} cannot throw to monkey {}", idx, target));
             }
-
-            next_monkey.items.borrow_mut().push_back(item);
+        }
+        if monkey.test_div == 0 {
+            return Err(format!("Monkey {} tests divisibility by 0", idx));
         }
     }
+
+    Ok(monkeys)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_parts() {
-        let test_contents = String::from_str(
-            "Monkey 0:
+    const EXAMPLE: &str = "Monkey 0:
   Starting items: 79, 98
   Operation: new = old * 19
   Test: divisible by 23
@@ -183,11 +532,185 @@ Monkey 3:
   Operation: new = old + 3
   Test: divisible by 17
     If true: throw to monkey 0
-    If false: throw to monkey 1",
-        )
-        .unwrap();
+    If false: throw to monkey 1";
+
+    #[test]
+    fn test_expr() {
+        let expr = Expr::from_str("old * (old - 2) + 10 / old").unwrap();
+        assert_eq!(expr.to_string(), "((old * (old - 2)) + (10 / old))");
+        assert_eq!(expr.eval(5, None), Ok(5 * 3 + 2));
+        assert!(expr.has_division());
+
+        let expr = Expr::from_str("old - 7").unwrap();
+        assert_eq!(expr.eval(3, None), Ok(0));
+        assert_eq!(expr.eval(3, Some(10)), Ok(6));
+        assert_eq!(Expr::from_str("old*old").unwrap().eval(9, Some(10)), Ok(1));
+
+        assert!(Expr::from_str("old ^ 2").is_err());
+        assert!(Expr::from_str("(old + 1").is_err());
+        assert!(Expr::from_str("old old").is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(Expr::from_str("old / 0"), Err("Division by 0".to_string()));
+        assert!(Expr::from_str("(old + 1) / (0)").is_err());
+        assert_eq!(Expr::from_str("0 / old").unwrap().eval(0, None).ok(), None);
+
+        let expr = Expr::from_str("old / (old - old)").unwrap();
+        assert_eq!(
+            expr.eval(4, None),
+            Err("(old - old) is 0 when old is 4, cannot divide by it".to_string())
+        );
+        assert_eq!(
+            Expr::from_str("old / (old - 3)").unwrap().eval(7, None),
+            Ok(1)
+        );
+
+        // the simulation stops on it instead of panicking
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        monkeys[2].operation = expr;
+        let mut simulation = Simulation::new(&monkeys, Relief::Divide(3)).unwrap();
+        assert_eq!(
+            simulation.run(1),
+            Err("Monkey 2: (old - old) is 0 when old is 79, cannot divide by it".to_string())
+        );
+        assert!(parse_monkeys(&EXAMPLE.replace("old + 6", "old / 0")).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let expr = Expr::from_str("old * old").unwrap();
+        assert_eq!(
+            expr.eval(u64::MAX, None),
+            Err(format!("(old * old) overflows when old is {}", u64::MAX))
+        );
+        assert_eq!(
+            Expr::from_str("old + 1").unwrap().eval(u64::MAX, None),
+            Err(format!("(old + 1) overflows when old is {}", u64::MAX))
+        );
+        // reduced by the modulus, it fits
+        assert_eq!(expr.eval(u64::MAX, Some(10)), Ok(5));
+
+        // the divisibility tests have no common multiple that fits
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        for (monkey, divisor) in monkeys.iter_mut().zip([4294967291, 4294967279, 13, 17]) {
+            monkey.test_div = divisor;
+        }
+        assert!(Relief::modulo_for(&monkeys).is_err());
+        monkeys[1].test_div = 4294967291;
+        assert_eq!(
+            Relief::modulo_for(&monkeys),
+            Ok(Relief::Modulo(4294967291 * 13 * 17))
+        );
+    }
+
+    #[test]
+    fn test_simulation() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+
+        let mut simulation = Simulation::new(&monkeys, Relief::Divide(3)).unwrap();
+        simulation.run(20).unwrap();
+        assert_eq!(simulation.inspections(), [101, 95, 7, 105]);
+
+        let relief = Relief::modulo_for(&monkeys).unwrap();
+        assert_eq!(relief, Relief::Modulo(23 * 19 * 13 * 17));
+        let mut simulation = Simulation::new(&monkeys, relief).unwrap();
+        simulation.run(1).unwrap();
+        assert_eq!(simulation.inspections(), [2, 4, 3, 6]);
+        simulation.run(19).unwrap();
+        assert_eq!(simulation.inspections(), [99, 97, 8, 103]);
+
+        let mut dividing = monkeys.clone();
+        dividing[1].operation = Expr::from_str("old / 2").unwrap();
+        assert!(Simulation::new(&dividing, relief).is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let mut simulation = Simulation::new(&monkeys, Relief::Divide(3))
+            .unwrap()
+            .traced();
+        simulation.run(20).unwrap();
+        let trace = simulation.trace().unwrap();
+        assert_eq!(
+            trace.steps.len() as u64,
+            simulation.inspections().iter().sum()
+        );
+
+        // the first item, 79 held by monkey 0, as in the puzzle's walkthrough
+        assert_eq!(
+            trace.path(0).next(),
+            Some(&Step {
+                round: 1,
+                item: 0,
+                monkey: 0,
+                worry: 500,
+                divisible: false,
+                thrown_to: 3,
+            })
+        );
+        assert_eq!(trace.holder(0, 0), Some(0));
+        // after round 1, all the items are held by monkeys 0 and 1
+        for item in 0..10 {
+            assert!(trace.holder(item, 1).unwrap() < 2);
+        }
+        assert_eq!(trace.holder(10, 1), None);
+
+        let mut csv = Vec::new();
+        trace.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("round,item,monkey,worry,divisible,thrown_to")
+        );
+        assert_eq!(lines.next(), Some("1,0,0,500,false,3"));
+        assert_eq!(lines.count(), trace.steps.len() - 1);
+
+        // a monkey throwing to the same monkey either way still records its test
+        let mut same_target = monkeys.clone();
+        same_target[0].if_true = 3;
+        let mut simulation = Simulation::new(&same_target, Relief::Divide(3))
+            .unwrap()
+            .traced();
+        simulation.run(1).unwrap();
+        let trace = simulation.trace().unwrap();
+        let thrown_by_0: Vec<(u64, bool, usize)> = trace
+            .steps
+            .iter()
+            .filter(|step| step.monkey == 0)
+            .map(|step| (step.worry, step.divisible, step.thrown_to))
+            .collect();
+        assert_eq!(thrown_by_0, [(500, false, 3), (620, false, 3)]);
+        assert!(
+            trace
+                .steps
+                .iter()
+                .all(|step| step.divisible
+                    == step.worry.is_multiple_of(monkeys[step.monkey].test_div))
+        );
+
+        // off by default
+        assert!(Simulation::new(&monkeys, Relief::Divide(3))
+            .unwrap()
+            .trace()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_monkeys(&EXAMPLE.replace("old + 6", "old % 6")).is_err());
+        assert!(parse_monkeys(&EXAMPLE.replace("monkey 3", "monkey 4")).is_err());
+        assert!(parse_monkeys(&EXAMPLE.replace("by 17", "by seventeen")).is_err());
+    }
+
+    #[test]
+    fn test_parts() {
+        let test_contents = String::from_str(EXAMPLE).unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents), 10605);
+        assert_eq!(part2(&test_contents, None, None), 2713310158);
     }
 }
@@ -1,259 +1,510 @@
+use serde_json::Value;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::str::FromStr;
 use std::vec::Vec;
 
-#[derive(Debug, PartialEq, Eq)]
-enum Outcome {
-    Right,
-    Wrong,
-    Undecided,
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Elem {
     Integer(u32),
     List(Vec<Elem>),
 }
 
+/// Compares following the puzzle's rules: integers by value, lists element by element with the
+/// shortest list first when one is a prefix of the other, and an integer as a list of one
+/// element when compared to a list.
+impl Ord for Elem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Elem::Integer(l), Elem::Integer(r)) => l.cmp(r),
+            (Elem::List(l), Elem::List(r)) => l.cmp(r),
+            (Elem::Integer(_), Elem::List(r)) => std::slice::from_ref(self).cmp(r),
+            (Elem::List(l), Elem::Integer(_)) => l.as_slice().cmp(std::slice::from_ref(other)),
+        }
+    }
+}
+
+impl PartialOrd for Elem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Equality follows the ordering, so `2` and `[[2]]` are equal.
+impl PartialEq for Elem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Elem {}
+
 impl fmt::Display for Elem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Elem::Integer(a) => {
-                write!(f, "{}", a);
+            Elem::Integer(a) => write!(f, "{}", a),
+            Elem::List(v) => write_list(f, v),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, items: &[Elem]) -> fmt::Result {
+    write!(f, "[")?;
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    write!(f, "]")
+}
+
+/// A packet is always a list. Ordering and equality are the ones of `Elem`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Packet(Vec<Elem>);
+
+impl Packet {
+    fn divider(value: u32) -> Self {
+        Packet(vec![Elem::List(vec![Elem::Integer(value)])])
+    }
+
+    /// Parses a packet that follows the grammar exactly: no whitespace, no leading zero.
+    fn parse_strict(s: &str) -> Result<Self, ParseError> {
+        Parser::new(s, true).packet()
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_list(f, &self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    /// `position` is the index of the offending byte in the packet
+    Unexpected {
+        position: usize,
+        expected: &'static str,
+        found: Option<char>,
+    },
+    Overflow {
+        position: usize,
+    },
+    /// Only in strict mode, as in JSON
+    LeadingZero {
+        position: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Unexpected {
+                position,
+                expected,
+                found: Some(c),
+            } => write!(f, "Expected {} at {}, found '{}'", expected, position, c),
+            ParseError::Unexpected {
+                position,
+                expected,
+                found: None,
+            } => write!(f, "Expected {} at {}, found the end", expected, position),
+            ParseError::Overflow { position } => {
+                write!(f, "Number at {} does not fit in 32 bits", position)
             }
-            Elem::List(v) => {
-                write!(f, "[");
-                for item in v.iter() {
-                    write!(f, "{}", item.to_string());
-                    write!(f, ",");
-                }
-                write!(f, "]");
+            ParseError::LeadingZero { position } => {
+                write!(f, "Number at {} starts with a 0", position)
             }
         }
-        Ok(())
     }
 }
 
-impl Elem {
-    fn is_integer(&self) -> bool {
-        match self {
-            Elem::Integer(_) => true,
-            _ => false,
+impl Error for ParseError {}
+
+/// Recursive-descent parser for the grammar
+/// `packet = list`, `list = "[" [elem {"," elem}] "]"`, `elem = list | integer`.
+///
+/// By default, whitespace between tokens and leading zeros are tolerated. In strict mode,
+/// anything outside of the grammar is rejected, which makes the packets a subset of JSON.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strict: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str, strict: bool) -> Self {
+        Parser {
+            bytes: s.as_bytes(),
+            position: 0,
+            strict,
         }
     }
 
-    fn is_list(&self) -> bool {
-        !self.is_integer()
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
     }
 
-    fn to_int(&self) -> Option<u32> {
-        match self {
-            Elem::Integer(a) => Some(*a),
-            _ => None,
+    /// The next byte, after the whitespace unless in strict mode.
+    fn peek_token(&mut self) -> Option<u8> {
+        if !self.strict {
+            while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+                self.position += 1;
+            }
         }
+        self.peek()
     }
 
-    fn to_list(&self) -> Option<&Vec<Elem>> {
-        match self {
-            Elem::List(a) => Some(a),
-            _ => None,
+    /// Parses a whole packet, with nothing after it.
+    fn packet(&mut self) -> Result<Packet, ParseError> {
+        let packet = Packet(self.list()?);
+        if self.peek_token().is_some() {
+            return Err(self.unexpected("the end of the packet"));
         }
+        Ok(packet)
     }
 
-    fn parse(contents: &str) -> Self {
-        let mut queue: Vec<Vec<Elem>> = Vec::new();
-        let mut current_vec: Vec<Elem> = Vec::new();
-        // processing of numbers is super ugly but seems to work
-        let mut current_num: Vec<char> = Vec::new();
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        ParseError::Unexpected {
+            position: self.position,
+            expected,
+            found: self.peek().map(char::from),
+        }
+    }
 
-        // remove the surrounding []
-        for c in contents[1..contents.len() - 1].chars() {
-            match c {
-                '[' => {
-                    queue.push(current_vec);
-                    current_vec = Vec::new();
-                }
-                ']' => {
-                    if current_num.len() > 0 {
-                        current_vec.push(Elem::Integer(
-                            current_num
-                                .into_iter()
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .unwrap(),
-                        ));
-                        current_num = Vec::new();
-                    }
-
-                    let mut parent = queue.pop().unwrap();
-                    parent.push(Elem::List(current_vec));
-                    current_vec = parent;
-                }
-                ',' => {
-                    if current_num.len() > 0 {
-                        current_vec.push(Elem::Integer(
-                            current_num
-                                .into_iter()
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .unwrap(),
-                        ));
-                        current_num = Vec::new();
-                    }
-                }
-                digit => {
-                    current_num.push(digit);
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), ParseError> {
+        if self.peek_token() != Some(byte) {
+            return Err(self.unexpected(expected));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn list(&mut self) -> Result<Vec<Elem>, ParseError> {
+        self.expect(b'[', "'['")?;
+        let mut items = Vec::new();
+        if self.peek_token() == Some(b']') {
+            self.position += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(self.elem()?);
+            match self.peek_token() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(items);
                 }
+                _ => return Err(self.unexpected("',' or ']'")),
             }
         }
-        // Process the last entry that had been excluded by truncating the surrounding []
-        if current_num.len() > 0 {
-            current_vec.push(Elem::Integer(
-                current_num
-                    .into_iter()
-                    .collect::<String>()
-                    .parse::<u32>()
-                    .unwrap(),
-            ));
+    }
+
+    fn elem(&mut self) -> Result<Elem, ParseError> {
+        match self.peek_token() {
+            Some(b'[') => Ok(Elem::List(self.list()?)),
+            Some(b'0'..=b'9') => self.integer(),
+            _ => Err(self.unexpected("'[' or a digit")),
         }
+    }
 
-        Self::List(current_vec)
+    fn integer(&mut self) -> Result<Elem, ParseError> {
+        let start = self.position;
+        let leading_zero = self.bytes[start] == b'0'
+            && self
+                .bytes
+                .get(start + 1)
+                .is_some_and(|b| b.is_ascii_digit());
+        if self.strict && leading_zero {
+            return Err(ParseError::LeadingZero { position: start });
+        }
+        let mut value: u32 = 0;
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((digit - b'0') as u32))
+                .ok_or(ParseError::Overflow { position: start })?;
+            self.position += 1;
+        }
+        Ok(Elem::Integer(value))
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+impl FromStr for Packet {
+    type Err = ParseError;
 
-    part1(&contents);
-    part2(&contents);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s, false).packet()
+    }
 }
 
-fn part1(contents: &String) {
-    let lines = contents.lines().collect::<Vec<&str>>();
-    let mut line_iter = lines.iter();
-    let mut idx = 1;
-    let mut score = 0;
-
-    loop {
-        // println!("\n\n###############\nPackets {}", idx);
-        let left_packet = line_iter.next().unwrap();
-        let right_packet = line_iter.next().unwrap();
+/// A JSON value that is not a packet, `path` telling where, e.g. `$[1][0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JsonError {
+    path: String,
+    found: String,
+}
 
-        let out = good_packet_order(&Elem::parse(&left_packet), &Elem::parse(&right_packet));
-        // println!("Packets {} - {:?}", idx, out);
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Expected a list or a 32 bit unsigned integer at {}, found {}",
+            self.path, self.found
+        )
+    }
+}
 
-        if out == Outcome::Right {
-            score += idx;
-        }
+impl Error for JsonError {}
 
-        let blank = line_iter.next();
-        if blank.is_none() {
-            break;
+impl From<&Elem> for Value {
+    fn from(elem: &Elem) -> Self {
+        match elem {
+            Elem::Integer(a) => Value::from(*a),
+            Elem::List(items) => Value::Array(items.iter().map(Value::from).collect()),
         }
-
-        idx += 1;
     }
+}
 
-    println!("Final score {}", score);
+impl From<&Packet> for Value {
+    fn from(packet: &Packet) -> Self {
+        Value::Array(packet.0.iter().map(Value::from).collect())
+    }
 }
 
-fn part2(contents: &String) {
-    let mut lines: Vec<Elem> = Vec::new();
-    for line in contents.lines() {
-        if line.len() == 0 {
-            continue;
+impl Elem {
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        match value {
+            Value::Array(items) => Ok(Elem::List(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| Elem::from_json(item, &format!("{}[{}]", path, idx)))
+                    .collect::<Result<Vec<Elem>, JsonError>>()?,
+            )),
+            Value::Number(n) => match n.as_u64().and_then(|n| u32::try_from(n).ok()) {
+                Some(n) => Ok(Elem::Integer(n)),
+                None => Err(JsonError {
+                    path: path.to_string(),
+                    found: n.to_string(),
+                }),
+            },
+            other => Err(JsonError {
+                path: path.to_string(),
+                found: other.to_string(),
+            }),
         }
-        lines.push(Elem::parse(line));
     }
+}
 
-    lines.push(Elem::List(vec![Elem::List(vec![Elem::Integer(2)])]));
-    lines.push(Elem::List(vec![Elem::List(vec![Elem::Integer(6)])]));
+impl TryFrom<&Value> for Packet {
+    type Error = JsonError;
 
-    lines.sort_by(|a, b| {
-        if good_packet_order(a, b) == Outcome::Right {
-            return Ordering::Less;
-        } else {
-            return Ordering::Greater;
-        }
-    });
-
-    let mut decoder = 1;
-    let mut idx = 1;
-    for line in lines.iter() {
-        let repr = line.to_string();
-        if repr == "[[2,],]" || repr == "[[6,],]" {
-            decoder *= idx;
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match Elem::from_json(value, "$")? {
+            Elem::List(items) => Ok(Packet(items)),
+            Elem::Integer(a) => Err(JsonError {
+                path: "$".to_string(),
+                found: a.to_string(),
+            }),
         }
-        println!("{}", repr);
+    }
+}
 
-        idx += 1;
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+
+    part1(&contents).expect("Could not read the packets");
+    part2(&contents).expect("Could not read the packets");
+}
+
+fn parse_packets(contents: &str) -> Result<Vec<Packet>, ParseError> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(Packet::parse_strict)
+        .collect()
+}
+
+fn part1(contents: &String) -> Result<usize, ParseError> {
+    let packets = parse_packets(contents)?;
+    let score = packets
+        .chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| pair.len() == 2 && pair[0] < pair[1])
+        .map(|(idx, _)| idx + 1)
+        .sum();
+
+    println!("Final score {}", score);
+    Ok(score)
+}
+
+fn part2(contents: &String) -> Result<usize, ParseError> {
+    let mut packets = parse_packets(contents)?;
+    packets.sort();
+    for packet in packets.iter() {
+        println!("{}", packet);
     }
 
+    // the position each divider would have, the first one shifting the second
+    let index_of = |divider: &Packet| match packets.binary_search(divider) {
+        Ok(idx) | Err(idx) => idx,
+    };
+    let decoder = (index_of(&Packet::divider(2)) + 1) * (index_of(&Packet::divider(6)) + 2);
+
     println!("Decoder value: {}", decoder);
+    Ok(decoder)
 }
 
-fn good_packet_order(left: &Elem, right: &Elem) -> Outcome {
-    // println!("Comparing {:?} and {:?}", left, right);
-    if left.is_integer() && right.is_integer() {
-        let l = left.to_int().unwrap();
-        let r = right.to_int().unwrap();
-        if l == r {
-            return Outcome::Undecided;
-        } else if l < r {
-            return Outcome::Right;
-        } else {
-            return Outcome::Wrong;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_packet_parse() {
+        for packet in [
+            "[[[]]]",
+            "[]",
+            "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+            "[10,[],4294967295]",
+        ] {
+            assert_eq!(Packet::from_str(packet).unwrap().to_string(), packet);
         }
+
+        assert_eq!(
+            Packet::from_str("[1,,2]"),
+            Err(ParseError::Unexpected {
+                position: 3,
+                expected: "'[' or a digit",
+                found: Some(',')
+            })
+        );
+        assert_eq!(
+            Packet::from_str("[[1]"),
+            Err(ParseError::Unexpected {
+                position: 4,
+                expected: "',' or ']'",
+                found: None
+            })
+        );
+        assert_eq!(
+            Packet::from_str("[1]]"),
+            Err(ParseError::Unexpected {
+                position: 3,
+                expected: "the end of the packet",
+                found: Some(']')
+            })
+        );
+        assert_eq!(
+            Packet::from_str("3"),
+            Err(ParseError::Unexpected {
+                position: 0,
+                expected: "'['",
+                found: Some('3')
+            })
+        );
+        assert_eq!(
+            Packet::from_str("[1,4294967296]"),
+            Err(ParseError::Overflow { position: 3 })
+        );
     }
 
-    if left.is_list() && right.is_list() {
-        let mut left_iter = left.to_list().unwrap().iter();
-        let mut right_iter = right.to_list().unwrap().iter();
+    #[test]
+    fn test_strict_mode() {
+        assert_eq!(
+            Packet::from_str(" [1, [ 02 ] ]").unwrap().to_string(),
+            "[1,[2]]"
+        );
+        assert_eq!(
+            Packet::parse_strict("[1, [2]]"),
+            Err(ParseError::Unexpected {
+                position: 3,
+                expected: "'[' or a digit",
+                found: Some(' ')
+            })
+        );
+        assert_eq!(
+            Packet::parse_strict("[1,[02]]"),
+            Err(ParseError::LeadingZero { position: 4 })
+        );
+        assert!(Packet::parse_strict("[0,[10]]").is_ok());
+    }
 
-        loop {
-            let n_left = left_iter.next();
-            let n_right = right_iter.next();
-
-            if n_left.is_none() && n_right.is_none() {
-                return Outcome::Undecided;
-            } else if n_left.is_none() && !n_right.is_none() {
-                return Outcome::Right;
-            } else if !n_left.is_none() && n_right.is_none() {
-                return Outcome::Wrong;
-            } else {
-                let out = good_packet_order(n_left.unwrap(), n_right.unwrap());
-                if out == Outcome::Undecided {
-                    continue;
-                } else {
-                    return out;
-                }
-            }
+    #[test]
+    fn test_json() {
+        let packet = Packet::from_str("[1,[2,[]],3]").unwrap();
+        assert_eq!(Value::from(&packet), json!([1, [2, []], 3]));
+        assert_eq!(
+            Packet::try_from(&json!([1, [2, []], 3]))
+                .unwrap()
+                .to_string(),
+            "[1,[2,[]],3]"
+        );
+
+        for (value, path, found) in [
+            (json!([1, [-2]]), "$[1][0]", "-2"),
+            (json!([[1.5]]), "$[0][0]", "1.5"),
+            (json!([4294967296u64]), "$[0]", "4294967296"),
+            (json!([[], "a"]), "$[1]", "\"a\""),
+            (json!({"a": 1}), "$", "{\"a\":1}"),
+            (json!(3), "$", "3"),
+        ] {
+            assert_eq!(
+                Packet::try_from(&value),
+                Err(JsonError {
+                    path: path.to_string(),
+                    found: found.to_string()
+                })
+            );
         }
     }
 
-    if left.is_list() && right.is_integer() {
-        return good_packet_order(
-            left,
-            &Elem::List(vec![Elem::Integer(right.to_int().unwrap())]),
-        );
-    } else {
-        return good_packet_order(
-            &Elem::List(vec![Elem::Integer(left.to_int().unwrap())]),
-            right,
-        );
+    fn elem() -> impl Strategy<Value = Elem> {
+        let leaf = prop_oneof![0u32..20, any::<u32>()].prop_map(Elem::Integer);
+        leaf.prop_recursive(5, 64, 6, |inner| {
+            prop::collection::vec(inner, 0..6).prop_map(Elem::List)
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    proptest! {
+        #[test]
+        fn test_packet_and_json_agree(items in prop::collection::vec(elem(), 0..6)) {
+            let text = Packet(items).to_string();
+            let from_json = Packet::try_from(&serde_json::from_str::<Value>(&text).unwrap());
+            let parsed = Packet::parse_strict(&text);
+
+            prop_assert_eq!(from_json.unwrap().to_string(), text.clone());
+            prop_assert_eq!(Value::from(&parsed.unwrap()).to_string(), text.clone());
+        }
+
+        #[test]
+        fn test_strict_parser_accepts_json_packets(s in "[\\[\\],0-9 -]{0,12}") {
+            let from_json = serde_json::from_str::<Value>(&s)
+                .ok()
+                .and_then(|value| Packet::try_from(&value).ok());
+            match Packet::parse_strict(&s) {
+                Ok(packet) => {
+                    prop_assert_eq!(Some(packet.to_string()), from_json.map(|p| p.to_string()))
+                }
+                // JSON allows whitespace
+                Err(_) => prop_assert!(from_json.is_none() || s.contains(' ')),
+            }
+        }
+    }
 
     #[test]
-    fn test_Elem_parse() {
-        dbg!(Elem::parse(&"[[[]]]"));
+    fn test_packet_order() {
+        let packet = |s: &str| Packet::from_str(s).unwrap();
+        assert!(packet("[[1],[2,3,4]]") < packet("[[1],4]"));
+        assert!(packet("[9]") > packet("[[8,7,6]]"));
+        assert_eq!(packet("[[2]]"), packet("[2]"));
+        assert_eq!(packet("[[2]]").cmp(&packet("[[[2]]]")), Ordering::Equal);
+        assert!(packet("[]") < packet("[[]]"));
     }
 
     #[test]
@@ -263,7 +514,7 @@ mod tests {
 [1,1,5,1,1]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(1));
     }
 
     #[test]
@@ -273,7 +524,7 @@ mod tests {
 [[1],4]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(1));
     }
 
     #[test]
@@ -283,7 +534,7 @@ mod tests {
 [[8,7,6]]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(0));
     }
 
     #[test]
@@ -293,7 +544,7 @@ mod tests {
 [[4,4],4,4,4]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(1));
     }
 
     #[test]
@@ -303,7 +554,7 @@ mod tests {
 [7,7,7]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(0));
     }
 
     #[test]
@@ -313,7 +564,7 @@ mod tests {
 [3]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(1));
     }
 
     #[test]
@@ -323,7 +574,7 @@ mod tests {
 [[]]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(0));
     }
 
     #[test]
@@ -333,7 +584,7 @@ mod tests {
 [1,[2,[3,[4,[5,6,0]]]],8,9]",
         )
         .unwrap();
-        part1(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(0));
     }
 
     #[test]
@@ -365,7 +616,7 @@ mod tests {
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents), Ok(13));
+        assert_eq!(part2(&test_contents), Ok(140));
     }
 }
@@ -1,8 +1,10 @@
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::error::Error;
 use std::fs;
-use std::str::FromStr;
 use std::vec::Vec;
+use utils::render::{self, Palette};
+use utils::viz::{Frame, Playback, Recorder};
 
 struct World {
     rocks_coord: HashSet<(i32, i32)>,
@@ -128,94 +130,239 @@ impl World {
         Ok(())
     }
 
-    fn display(&self) {
-        for y in self.min_y..=self.max_y {
-            for x in self.min_x..=self.max_x {
-                if y == 0 && x == 500 {
-                    if self.sands_coord.get(&(x, y)).is_some() {
-                        print!("X");
-                    } else {
-                        print!("+");
-                    }
-                } else if x == self.moving_sand_coord.0 && y == self.moving_sand_coord.1 {
-                    print!("o");
-                } else if self.sands_coord.get(&(x, y)).is_some() {
-                    print!("O");
-                } else if self.rocks_coord.get(&(x, y)).is_some() {
-                    print!("#");
-                } else {
-                    print!(".");
-                }
-            }
-            println!("");
+    fn cells(&self) -> HashMap<(i64, i64), char> {
+        let mut cells = HashMap::new();
+        for (x, y) in self.rocks_coord.iter() {
+            cells.insert((*x as i64, *y as i64), '#');
+        }
+        for (x, y) in self.sands_coord.iter() {
+            cells.insert((*x as i64, *y as i64), 'O');
         }
+        let (x, y) = self.moving_sand_coord;
+        cells.insert((x as i64, y as i64), 'o');
+        if self.sands_coord.contains(&(500, 0)) {
+            cells.insert((500, 0), 'X');
+        } else {
+            cells.insert((500, 0), '+');
+        }
+        cells
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
-
-    part1(&contents);
-    part2(&contents);
+/// Colors of the cells drawn by `World::cells`, for `--render`
+fn palette() -> Palette<char> {
+    Palette::new([20, 20, 30])
+        .with('#', [130, 130, 130])
+        .with('O', [194, 178, 128])
+        .with('o', [255, 140, 0])
+        .with('+', [220, 40, 60])
+        .with('X', [220, 40, 60])
 }
 
-fn part1(contents: &String) {
-    let mut world = World::new();
-    for line in contents.lines() {
-        parse_line(line, &mut world);
+fn replay(recorder: Option<Recorder>, playback: Option<&Playback>, part: u32) {
+    if let (Some(recorder), Some(playback)) = (recorder, playback) {
+        recorder
+            .replay(playback)
+            .expect("Could not replay the simulation");
+        render::export(&recorder, &playback.for_part(part), &palette())
+            .expect("Could not export the simulation");
     }
+}
 
-    world.display();
+/// Where a grain ends up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Drop {
+    Settled((i32, i32)),
+    /// Fell below the lowest rock, when there is no floor
+    Abyss,
+    /// The source is covered, nothing can fall anymore
+    Clogged,
+}
 
-    let mut step = 1;
-    loop {
-        println!("Step {}", step);
+/// Fast sand simulator on a dense grid.
+///
+/// Grains follow the path of the previous one until the cell where it settled, so the path is
+/// kept as a stack and each grain starts from the top of it instead of from the source.
+struct Cave {
+    min_x: i32,
+    width: usize,
+    blocked: Vec<bool>,
+    // lowest rock
+    max_y: i32,
+    floor: bool,
+    path: Vec<(i32, i32)>,
+    settled: usize,
+}
 
-        match world.move_step() {
-            Ok(_) => (),
-            Err(a) => {
-                println!("End with: {}", a);
-                break;
+impl Cave {
+    const SOURCE: (i32, i32) = (500, 0);
+
+    /// With `floor`, there is an infinite floor 2 below the lowest rock.
+    fn new(rocks: &HashSet<(i32, i32)>, floor: bool) -> Self {
+        let max_y = rocks.iter().map(|(_, y)| *y).max().unwrap_or(0);
+        // sand spreads at most one cell sideways per row
+        let reach = max_y + 3;
+        let min_x = rocks.iter().map(|(x, _)| *x).fold(500 - reach, i32::min);
+        let max_x = rocks.iter().map(|(x, _)| *x).fold(500 + reach, i32::max);
+        let width = (max_x - min_x + 1) as usize;
+
+        let mut cave = Cave {
+            min_x,
+            width,
+            blocked: vec![false; width * (max_y + 3) as usize],
+            max_y,
+            floor,
+            path: vec![Self::SOURCE],
+            settled: 0,
+        };
+        for rock in rocks.iter() {
+            cave.block(*rock);
+        }
+        if floor {
+            for x in min_x..=max_x {
+                cave.block((x, max_y + 2));
             }
         }
+        cave
+    }
 
-        // world.display();
+    fn index(&self, (x, y): (i32, i32)) -> usize {
+        y as usize * self.width + (x - self.min_x) as usize
+    }
 
-        step += 1;
+    fn block(&mut self, coord: (i32, i32)) {
+        let idx = self.index(coord);
+        self.blocked[idx] = true;
+    }
+
+    fn is_blocked(&self, coord: (i32, i32)) -> bool {
+        self.blocked[self.index(coord)]
+    }
+
+    fn drop_grain(&mut self) -> Drop {
+        let mut pos = match self.path.last() {
+            Some(pos) => *pos,
+            None => return Drop::Clogged,
+        };
+        loop {
+            if !self.floor && pos.1 >= self.max_y {
+                return Drop::Abyss;
+            }
+            let next = [(0, 1), (-1, 1), (1, 1)]
+                .map(|(dx, dy)| (pos.0 + dx, pos.1 + dy))
+                .into_iter()
+                .find(|next| !self.is_blocked(*next));
+            match next {
+                Some(next) => {
+                    self.path.push(next);
+                    pos = next;
+                }
+                None => {
+                    self.block(pos);
+                    self.path.pop();
+                    self.settled += 1;
+                    return Drop::Settled(pos);
+                }
+            }
+        }
+    }
+
+    /// Drops grains until they fall in the abyss or clog the source, and counts the settled ones.
+    fn fill(&mut self) -> usize {
+        while let Drop::Settled(_) = self.drop_grain() {}
+        self.settled
     }
+}
 
-    world.display();
-    println!("\nNb settled sand {}", world.sands_coord.len());
+/// Counts the grains that settle above an infinite floor without simulating them: a cell gets
+/// sand if it is free and one of the three cells above it got some, so the sand is a triangle
+/// under the source, minus the rocks and the cells they shadow.
+fn count_with_floor(rocks: &HashSet<(i32, i32)>) -> usize {
+    let floor = rocks.iter().map(|(_, y)| *y).max().unwrap_or(0) + 2;
+    let min_x = Cave::SOURCE.0 - floor - 1;
+    let width = (2 * floor + 3) as usize;
+
+    let mut row = vec![false; width];
+    row[(Cave::SOURCE.0 - min_x) as usize] = true;
+    let mut count = 1;
+    for y in 1..floor {
+        let next: Vec<bool> = (0..width)
+            .map(|i| {
+                let above = row[i.saturating_sub(1)..(i + 2).min(width)]
+                    .iter()
+                    .any(|s| *s);
+                above && !rocks.contains(&(min_x + i as i32, y))
+            })
+            .collect();
+        count += next.iter().filter(|s| **s).count();
+        row = next;
+    }
+    count
 }
 
-fn part2(contents: &String) {
+fn parse_world(contents: &str) -> World {
     let mut world = World::new();
     for line in contents.lines() {
         parse_line(line, &mut world);
     }
-    world.add_floor();
+    world
+}
 
-    world.display();
+/// Runs the original step-by-step simulation, recording a frame per step to replay it.
+fn animate(mut world: World, playback: &Playback, part: u32) -> usize {
+    let mut recorder = playback.recorder();
+    if part == 2 {
+        world.add_floor();
+    }
 
     let mut step = 1;
     loop {
-        println!("Step {}", step);
-
-        match world.move_step_part2() {
-            Ok(_) => (),
-            Err(a) => {
-                println!("End with: {}", a);
-                break;
-            }
+        let moved = match part {
+            1 => world.move_step(),
+            _ => world.move_step_part2(),
+        };
+        if let Err(a) = moved {
+            println!("End with: {}", a);
+            break;
         }
 
-        // world.display();
+        recorder.record(|| (format!("Step {}", step), Frame::Cells(world.cells())));
 
         step += 1;
     }
 
-    world.display();
-    println!("\nNb settled sand {}", world.sands_coord.len());
+    replay(Some(recorder), Some(playback), part);
+    world.sands_coord.len()
+}
+
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+    let playback = Playback::from_args(env::args()).expect("Bad arguments");
+
+    part1(&contents, playback.as_ref());
+    part2(&contents, playback.as_ref());
+}
+
+fn part1(contents: &String, playback: Option<&Playback>) -> usize {
+    let world = parse_world(contents);
+    let settled = match playback {
+        Some(playback) => animate(world, playback, 1),
+        None => Cave::new(&world.rocks_coord, false).fill(),
+    };
+
+    println!("\nNb settled sand {}", settled);
+    settled
+}
+
+fn part2(contents: &String, playback: Option<&Playback>) -> usize {
+    let world = parse_world(contents);
+    let settled = match playback {
+        Some(playback) => animate(world, playback, 2),
+        None => count_with_floor(&world.rocks_coord),
+    };
+
+    println!("\nNb settled sand {}", settled);
+    settled
 }
 
 fn parse_line(line: &str, world: &mut World) {
@@ -249,6 +396,30 @@ fn parse_line(line: &str, world: &mut World) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_cells() {
+        let mut world = World::new();
+        parse_line("498,4 -> 498,6 -> 496,6", &mut world);
+        parse_line("503,4 -> 502,4 -> 502,9 -> 494,9", &mut world);
+
+        let mut recorder = Recorder::new();
+        recorder.push("Start".to_string(), Frame::Cells(world.cells()));
+        assert_eq!(
+            recorder.render(0).unwrap(),
+            "......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+........#.
+#########."
+        );
+    }
 
     #[test]
     fn test_parts() {
@@ -258,7 +429,59 @@ mod tests {
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents, None), 24);
+        assert_eq!(part2(&test_contents, None), 93);
+    }
+
+    /// Runs the original simulator to the end.
+    fn slow_count(contents: &str, floor: bool) -> usize {
+        let mut world = parse_world(contents);
+        if floor {
+            world.add_floor();
+            while world.move_step_part2().is_ok() {}
+        } else {
+            while world.move_step().is_ok() {}
+        }
+        world.sands_coord.len()
+    }
+
+    #[test]
+    fn test_fast_simulation() {
+        for contents in [
+            "498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9",
+            // a cup that overflows, and rocks far away from the source
+            "497,6 -> 497,8 -> 503,8 -> 503,6\n400,10 -> 401,10",
+            "500,2 -> 500,2",
+        ] {
+            let world = parse_world(contents);
+            let mut cave = Cave::new(&world.rocks_coord, false);
+            assert_eq!(cave.fill(), slow_count(contents, false), "{}", contents);
+            assert_eq!(cave.drop_grain(), Drop::Abyss);
+
+            let mut cave = Cave::new(&world.rocks_coord, true);
+            assert_eq!(cave.fill(), slow_count(contents, true), "{}", contents);
+            assert_eq!(cave.drop_grain(), Drop::Clogged);
+            assert_eq!(count_with_floor(&world.rocks_coord), cave.settled);
+        }
+    }
+
+    #[test]
+    fn test_clogged_without_floor() {
+        // the original simulator never stops when the sand reaches the source above a cup
+        let world = parse_world("497,3 -> 497,5 -> 503,5 -> 503,3");
+        let mut cave = Cave::new(&world.rocks_coord, false);
+        assert_eq!(cave.fill(), 5 * 2 + 5 + 3 + 1);
+        assert_eq!(cave.drop_grain(), Drop::Clogged);
+    }
+
+    #[test]
+    fn test_drop_grain() {
+        let world = parse_world("498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9");
+        let mut cave = Cave::new(&world.rocks_coord, false);
+        assert_eq!(cave.drop_grain(), Drop::Settled((500, 8)));
+        assert_eq!(cave.drop_grain(), Drop::Settled((499, 8)));
+        assert_eq!(cave.drop_grain(), Drop::Settled((501, 8)));
+        // resumes from the path of the previous grain
+        assert_eq!(cave.path.last(), Some(&(500, 7)));
     }
 }
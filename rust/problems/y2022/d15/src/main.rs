@@ -1,7 +1,6 @@
 use regex::Regex;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::str::FromStr;
 use std::vec::Vec;
 
 struct World {
@@ -13,6 +12,60 @@ struct World {
     max_y: i32,
 }
 
+/// An area of the map, bounds included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BoundingBox {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl BoundingBox {
+    /// The area from `(0, 0)` to `(bound, bound)`.
+    fn square(bound: i32) -> Self {
+        BoundingBox {
+            min_x: 0,
+            min_y: 0,
+            max_x: bound,
+            max_y: bound,
+        }
+    }
+
+    fn contains(&self, (x, y): (i32, i32)) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+}
+
+/// What the sensors tell about a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RowScan {
+    /// Sorted, disjoint ranges of positions seen by a sensor
+    ranges: Vec<(i32, i32)>,
+    /// Number of positions where the distress beacon cannot be
+    excluded: u64,
+}
+
+/// The positions of an area where the distress beacon can be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Search {
+    candidates: Vec<(i32, i32)>,
+}
+
+impl Search {
+    /// The position of the beacon, when there is only one candidate.
+    fn beacon(&self) -> Option<(i32, i32)> {
+        match self.candidates[..] {
+            [beacon] => Some(beacon),
+            _ => None,
+        }
+    }
+
+    fn tuning_frequency(&self) -> Option<u64> {
+        self.beacon().map(tuning_frequency)
+    }
+}
+
 impl World {
     fn new() -> Self {
         World {
@@ -71,42 +124,39 @@ impl World {
         }
     }
 
-    fn nb_beacons_and_sensors_at(&self, y: i32) -> i32 {
-        let mut tot = 0;
-        for beacon in self.beacons.iter() {
-            if beacon.1 == y {
-                tot += 1;
-            }
+    /// Scans the row `y`, limited to the columns of `area` if given. Known beacons are counted as
+    /// excluded positions only if `count_beacons` is set.
+    fn scan_row(&self, y: i32, area: Option<&BoundingBox>, count_beacons: bool) -> RowScan {
+        let ranges: Vec<(i32, i32)> = self
+            .scanned_pos_with_ranges(y, area.map(|a| (a.min_x, a.max_x)))
+            .into_iter()
+            .filter(|r| r.0 <= r.1)
+            .collect();
+        let mut excluded: u64 = ranges.iter().map(|r| (r.1 - r.0 + 1) as u64).sum();
+        if !count_beacons {
+            excluded -= self
+                .beacons
+                .iter()
+                .filter(|(x, by)| *by == y && ranges.iter().any(|r| (r.0..=r.1).contains(x)))
+                .count() as u64;
         }
-        for (sensor, _) in self.sensors.iter() {
-            if sensor.1 == y {
-                tot += 1;
-            }
-        }
-        tot
+        RowScan { ranges, excluded }
     }
 
-    fn scanned_pos_with_ranges(&self, y: i32, clip: bool) -> VecDeque<(i32, i32)> {
-        let min_boundary = 0;
-        let max_boundary = 4000000;
+    fn scanned_pos_with_ranges(&self, y: i32, clip: Option<(i32, i32)>) -> VecDeque<(i32, i32)> {
         let mut ranges: VecDeque<(i32, i32)> = VecDeque::new();
 
-        for (i, (sensor, sensor_view)) in self.sensors.iter().enumerate() {
-            // println!(
-            //     "\n================\nRunning sensor {} at {:?}, with view {}, for line {}",
-            //     i, sensor, sensor_view, y
-            // );
+        for (sensor, sensor_view) in self.sensors.iter() {
             let sensor_range = sensor_range_for_line(&sensor, *sensor_view, y);
-            // println!("Sees {:?} at line {}", sensor_range, y);
             match sensor_range {
                 None => continue,
                 Some(range) => {
-                    let clipped: (i32, i32);
-                    if clip {
-                        clipped = clip_range(range, min_boundary, max_boundary);
-                    } else {
-                        clipped = range;
-                    }
+                    let clipped = match clip {
+                        Some((min_boundary, max_boundary)) => {
+                            clip_range(range, min_boundary, max_boundary)
+                        }
+                        None => range,
+                    };
                     if ranges.len() == 0 {
                         ranges.push_back(clipped);
                         continue;
@@ -118,25 +168,15 @@ impl World {
                         match cur_range {
                             None => new_ranges.push_back(r),
                             Some(range) => {
-                                // println!("Comparing {:?} and {:?}", &range, &r);
                                 if are_overlapping_or_touching(range, r) {
                                     let merged = merge(range, r);
-                                    // println!("Overlapping, result in {:?}", &merged);
                                     cur_range = Some(merged);
                                 } else {
                                     if r.0 < range.0 {
-                                        // println!(
-                                        //     "Non overlapping, pushing {:?} keeping {:?}",
-                                        //     &r, &range
-                                        // );
                                         new_ranges.push_back(r);
                                         // we continue to try and fit our new range in the ranges
                                         // list
                                     } else {
-                                        // println!(
-                                        //     "Non verlapping, pushing {:?} then {:?}",
-                                        //     &range, &r
-                                        // );
                                         new_ranges.push_back(range);
                                         new_ranges.push_back(r);
                                         cur_range = None;
@@ -152,15 +192,69 @@ impl World {
                         Some(a) => new_ranges.push_back(a),
                     }
                     ranges = new_ranges;
-                    // println!("New temp ranges {:?}", ranges);
                 }
             }
         }
 
-        // println!("For {} found ranges {:?}", y, ranges);
         ranges
     }
 
+    fn is_seen(&self, coord: (i32, i32)) -> bool {
+        self.sensors
+            .iter()
+            .any(|(sensor, view_dist)| manhattan_dist(*sensor, coord) <= *view_dist)
+    }
+
+    /// Positions of `area` that no sensor sees, when there are only a few.
+    ///
+    /// Such a position is just out of the view of the sensors around it, so it lies on their
+    /// perimeters, the lines at distance `view + 1`: the candidates are the intersections of
+    /// these lines, between them and with the border of the area, plus its corners. Bigger
+    /// unseen areas are only found through their corners.
+    fn search(&self, area: &BoundingBox) -> Search {
+        let (min_x, min_y) = (area.min_x as i64, area.min_y as i64);
+        let (max_x, max_y) = (area.max_x as i64, area.max_y as i64);
+        // x + y = c and x - y = c
+        let mut descending: HashSet<i64> = HashSet::new();
+        let mut ascending: HashSet<i64> = HashSet::new();
+        for ((x, y), view_dist) in self.sensors.iter() {
+            let (x, y, d) = (*x as i64, *y as i64, *view_dist as i64 + 1);
+            descending.extend([x + y - d, x + y + d]);
+            ascending.extend([x - y - d, x - y + d]);
+        }
+
+        let mut candidates: Vec<(i64, i64)> = vec![
+            (min_x, min_y),
+            (min_x, max_y),
+            (max_x, min_y),
+            (max_x, max_y),
+        ];
+        for a in descending.iter() {
+            for b in ascending.iter() {
+                if (a + b) % 2 == 0 {
+                    candidates.push(((a + b) / 2, (a - b) / 2));
+                }
+            }
+        }
+        for x in [min_x, max_x] {
+            candidates.extend(descending.iter().map(|c| (x, c - x)));
+            candidates.extend(ascending.iter().map(|c| (x, x - c)));
+        }
+        for y in [min_y, max_y] {
+            candidates.extend(descending.iter().map(|c| (c - y, y)));
+            candidates.extend(ascending.iter().map(|c| (c + y, y)));
+        }
+
+        let mut candidates: Vec<(i32, i32)> = candidates
+            .into_iter()
+            .filter_map(|(x, y)| Some((i32::try_from(x).ok()?, i32::try_from(y).ok()?)))
+            .filter(|coord| area.contains(*coord) && !self.is_seen(*coord))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Search { candidates }
+    }
+
     fn scanned_pos_slow(&self, y: i32) -> u32 {
         let mut pos = 0;
 
@@ -263,67 +357,46 @@ fn manhattan_dist(a: (i32, i32), b: (i32, i32)) -> i32 {
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    part1(&contents);
-    part2(&contents);
-}
-
-fn part1(contents: &String) {
-    let mut world = World::new();
-
-    for line in contents.lines() {
-        world.add_result(line);
+    println!("Part 1: {}", part1(&contents, 2000000));
+    match part2(&contents, &BoundingBox::square(4000000)) {
+        Some(frequency) => println!("Part 2: {}", frequency),
+        None => println!("Part 2: no single position for the distress beacon"),
     }
+}
 
-    // world.display();
-    for y in [0, 10, 11, 2000000] {
-        let ranges = world.scanned_pos_with_ranges(y, false);
-        println!("\nline {}", y);
-        let mut tot = 0;
-        for r in ranges.iter() {
-            tot += r.1 - r.0 + 1;
-        }
-        tot -= world.nb_beacons_and_sensors_at(y);
-        println!("scanned lines {}", tot);
-    }
+fn tuning_frequency((x, y): (i32, i32)) -> u64 {
+    x as u64 * 4000000 + y as u64
 }
 
-fn part2(contents: &String) {
+fn parse_world(contents: &str) -> World {
     let mut world = World::new();
-
     for line in contents.lines() {
         world.add_result(line);
     }
+    world
+}
 
-    for y in 0..=4000000 {
-        let ranges = world.scanned_pos_with_ranges(y, true);
-        if ranges.len() == 0 {
-            continue;
-        }
-        if ranges.len() == 1 {
-            continue;
-        }
-        if ranges.len() > 2 {
-            println!("{:?}", ranges);
-            panic!("WTF");
-        }
+/// Counts the positions of row `y` where the distress beacon cannot be.
+fn part1(contents: &String, y: i32) -> u64 {
+    parse_world(contents).scan_row(y, None, false).excluded
+}
 
-        let x = ranges[0].1 + 1;
-        assert!(
-            x == ranges[1].0 - 1,
-            "At y {} => x: {} == {}, ranges {:?}",
-            y,
-            x,
-            ranges[1].0 - 1,
-            ranges
+/// Finds the tuning frequency of the only position in `area` where the distress beacon can be.
+fn part2(contents: &String, area: &BoundingBox) -> Option<u64> {
+    let search = parse_world(contents).search(area);
+    if search.beacon().is_none() {
+        println!(
+            "Expected a single possible beacon, found {:?}",
+            search.candidates
         );
-        println!("Found possible beacon at ({},{})", x, y);
-        println!("Tuning at frequency {}", x as u64 * 4000000 + y as u64);
     }
+    search.tuning_frequency()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_sensor_range() {
@@ -349,6 +422,30 @@ mod tests {
         assert!(merge((1, 4), (2, 3)) == (1, 4));
     }
 
+    #[test]
+    fn test_hidden_positions() {
+        // hidden in a corner of the area
+        let mut world = World::new();
+        world.add_result("Sensor at x=2, y=2: closest beacon is at x=2, y=5");
+        assert_eq!(world.search(&BoundingBox::square(3)).candidates, [(0, 0)]);
+
+        // hidden against its border
+        let mut world = World::new();
+        world.add_result("Sensor at x=2, y=0: closest beacon is at x=2, y=3");
+        world.add_result("Sensor at x=2, y=4: closest beacon is at x=2, y=1");
+        world.add_result("Sensor at x=4, y=2: closest beacon is at x=4, y=4");
+        assert_eq!(world.search(&BoundingBox::square(4)).beacon(), Some((0, 2)));
+
+        // the area does not have to start at the origin
+        let area = BoundingBox {
+            min_x: 0,
+            min_y: 1,
+            max_x: 4,
+            max_y: 3,
+        };
+        assert_eq!(world.search(&area).candidates, [(0, 2)]);
+    }
+
     #[test]
     fn test_parts() {
         let test_contents = String::from_str(
@@ -369,7 +466,17 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3",
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents, 10), 26);
+        assert_eq!(
+            part2(&test_contents, &BoundingBox::square(20)),
+            Some(56000011)
+        );
+
+        let world = parse_world(&test_contents);
+        let scan = world.scan_row(10, None, true);
+        assert_eq!(scan.ranges, [(-2, 24)]);
+        assert_eq!(scan.excluded, 27);
+        let scan = world.scan_row(10, Some(&BoundingBox::square(20)), false);
+        assert_eq!((scan.ranges, scan.excluded), (vec![(0, 20)], 20));
     }
 }
@@ -1,125 +1,13 @@
 use regex::Regex;
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::str::FromStr;
 use std::vec::Vec;
-
-#[derive(Debug)]
-struct WorldState {
-    time: u32,
-    flow_rate: u32,
-    released: u32,
-    position: String,
-    open_valves: HashSet<String>,
-    paths_taken: HashSet<(String, String)>,
-}
-
-impl WorldState {
-    fn new() -> Self {
-        WorldState {
-            time: 0,
-            flow_rate: 0,
-            released: 0,
-            position: "AA".to_string(),
-            open_valves: HashSet::new(),
-            paths_taken: HashSet::new(),
-        }
-    }
-
-    fn is_done(&self) -> bool {
-        self.time == 30
-    }
-
-    fn add_one_minute(&self, valve_system: &ValveSystem) -> (Vec<Self>, bool) {
-        println_dbg(format!("\n\n===================\nAdding on minute to {:?}", self).as_str());
-        let mut new_worlds = Vec::new();
-
-        let new_time = self.time + 1;
-
-        // try to open a valve
-        if self.open_valves.get(&self.position).is_none() {
-            println_dbg(format!("Opening valve at {}", &self.position).as_str());
-            let added_flow_rate = valve_system.valve_flow.get(&self.position).unwrap();
-            if *added_flow_rate != 0 {
-                let mut new_open_valves = self.open_valves.clone();
-                new_open_valves.insert(self.position.clone());
-                new_worlds.push(WorldState {
-                    time: new_time,
-                    flow_rate: self.flow_rate + added_flow_rate,
-                    released: self.released + self.flow_rate,
-                    position: self.position.clone(),
-                    open_valves: new_open_valves,
-                    paths_taken: self.paths_taken.clone(),
-                });
-                println_dbg(
-                    format!(
-                        "Opened valve at {}, and produced {:?}",
-                        &self.position,
-                        &new_worlds[new_worlds.len() - 1]
-                    )
-                    .as_str(),
-                );
-            } else {
-                println_dbg(
-                    format!("No flow for valve at {}, not opening", &self.position).as_str(),
-                );
-            }
-        }
-
-        // try to move
-        for valve in valve_system.valve_paths.get(&self.position).unwrap().iter() {
-            if self
-                .paths_taken
-                .get(&(self.position.clone(), valve.clone()))
-                .is_some()
-            {
-                println_dbg(
-                    format!(
-                        "Already taken path at {} -> {}, not going there",
-                        &self.position.clone(),
-                        &valve.clone()
-                    )
-                    .as_str(),
-                );
-                continue;
-            }
-            let mut new_paths_taken = self.paths_taken.clone();
-            new_paths_taken.insert((self.position.clone(), valve.clone()));
-            new_worlds.push(WorldState {
-                time: new_time,
-                flow_rate: self.flow_rate,
-                released: self.released + self.flow_rate,
-                position: valve.clone(),
-                open_valves: self.open_valves.clone(),
-                paths_taken: new_paths_taken,
-            });
-            println_dbg(
-                format!(
-                    "Followed path {}, and produced {:?}",
-                    &valve,
-                    &new_worlds[new_worlds.len() - 1]
-                )
-                .as_str(),
-            );
-        }
-
-        if new_worlds.len() == 0 {
-            // don't move, do nothing
-            new_worlds.push(WorldState {
-                time: new_time,
-                flow_rate: self.flow_rate,
-                released: self.released + self.flow_rate,
-                position: self.position.clone(),
-                open_valves: self.open_valves.clone(),
-                paths_taken: self.paths_taken.clone(),
-            });
-        }
-
-        (new_worlds, new_time == 30)
-    }
-}
+use utils::{debug, trace};
 
 #[derive(Debug)]
 struct ValveSystem {
@@ -141,7 +29,7 @@ impl ValveSystem {
         let re = Regex::new(r"Valve (.{2}) has flow rate=(\d+); tunnels? leads? to valves? (.*)")
             .unwrap();
 
-        println_dbg(desc);
+        trace!("{}", desc);
         let cap = re.captures_iter(desc).next().unwrap();
         let valve_name = cap[1].to_string();
         let valve_flow = cap[2].parse::<u32>().unwrap();
@@ -156,36 +44,470 @@ impl ValveSystem {
         }
         self.valve_paths.insert(valve_name.clone(), valve_paths);
     }
+
+    /// Minutes needed to walk from `from` to each valve it leads to.
+    fn distances_from<'a>(&'a self, from: &'a str) -> HashMap<&'a str, u32> {
+        let mut distances: HashMap<&str, u32> = HashMap::new();
+        let mut queue: VecDeque<(&str, u32)> = VecDeque::from([(from, 0)]);
+        while let Some((valve, dist)) = queue.pop_front() {
+            if distances.contains_key(valve) {
+                continue;
+            }
+            distances.insert(valve, dist);
+            for next in self.valve_paths.get(valve).into_iter().flatten() {
+                queue.push_back((next, dist + 1));
+            }
+        }
+        distances
+    }
+}
+
+/// A set of useful valves, as a bit mask of their indices in a `Network`.
+type ValveSet = u64;
+
+/// Useful valves opened by an agent, in order, with the minute each one is open at.
+type Openings = Vec<(usize, u32)>;
+
+/// The best openings found for each set of valves, with the pressure they release.
+type PlansBySet = HashMap<ValveSet, (u32, Openings)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SolveError {
+    UnknownValve(String),
+    /// Valve sets only hold 64 useful valves
+    TooManyValves(usize),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::UnknownValve(name) => write!(f, "Unknown valve {}", name),
+            SolveError::TooManyValves(n) => {
+                write!(f, "Can only solve for 64 useful valves, found {}", n)
+            }
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+/// A valve opened at the end of `minute`, so releasing pressure from the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Opening {
+    minute: u32,
+    valve: String,
+}
+
+/// The valves opened by one agent, in order, and the pressure they release.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Plan {
+    released: u32,
+    schedule: Vec<Opening>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let openings: Vec<String> = self
+            .schedule
+            .iter()
+            .map(|o| format!("{}@{}", o.valve, o.minute))
+            .collect();
+        write!(f, "{} ({})", openings.join(" "), self.released)
+    }
+}
+
+/// Someone opening valves, from `start` and for `budget` minutes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Agent {
+    start: String,
+    budget: u32,
+}
+
+impl Agent {
+    fn new(start: &str, budget: u32) -> Self {
+        Agent {
+            start: start.to_string(),
+            budget,
+        }
+    }
+}
+
+impl FromStr for Agent {
+    type Err = String;
+
+    /// Reads `AA:26`, the start valve and the budget.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, budget) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected start:budget, found {}", s))?;
+        let budget = budget
+            .parse::<u32>()
+            .map_err(|e| format!("Bad budget in {}: {}", s, e))?;
+        Ok(Agent::new(start, budget))
+    }
+}
+
+/// The plans of all the agents, in their order, and the pressure they release together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Solution {
+    released: u32,
+    plans: Vec<Plan>,
 }
 
-fn print_dbg(msg: &str) {
-    if env::var("DEBUG").is_ok() {
-        print!("{}", msg);
+/// The valve system reduced to its useful valves and the start valves, with the time it takes
+/// to walk between them. Useful valves come first, so that their index fits in a `ValveSet`.
+struct Network {
+    names: Vec<String>,
+    flows: Vec<u32>,
+    useful: usize,
+    /// Minutes to walk between two valves, `u32::MAX` when there is no way
+    dist: Vec<Vec<u32>>,
+}
+
+impl Network {
+    fn new(system: &ValveSystem, starts: &[&str]) -> Result<Self, SolveError> {
+        let mut names: Vec<String> = system.useful_valves.iter().cloned().collect();
+        names.sort();
+        let useful = names.len();
+        if useful > ValveSet::BITS as usize {
+            return Err(SolveError::TooManyValves(useful));
+        }
+        for start in starts {
+            if !system.valve_flow.contains_key(*start) {
+                return Err(SolveError::UnknownValve(start.to_string()));
+            }
+            if !names.iter().any(|n| n == start) {
+                names.push(start.to_string());
+            }
+        }
+
+        let flows = names.iter().map(|n| system.valve_flow[n]).collect();
+        let dist = names
+            .iter()
+            .map(|from| {
+                let distances = system.distances_from(from);
+                names
+                    .iter()
+                    .map(|to| *distances.get(to.as_str()).unwrap_or(&u32::MAX))
+                    .collect()
+            })
+            .collect();
+        Ok(Network {
+            names,
+            flows,
+            useful,
+            dist,
+        })
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, SolveError> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| SolveError::UnknownValve(name.to_string()))
+    }
+
+    fn plan(&self, budget: u32, openings: &[(usize, u32)]) -> Plan {
+        Plan {
+            released: openings
+                .iter()
+                .map(|(valve, minute)| self.flows[*valve] * (budget - minute))
+                .sum(),
+            schedule: openings
+                .iter()
+                .map(|(valve, minute)| Opening {
+                    minute: *minute,
+                    valve: self.names[*valve].clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The minute at which `valve` is open when leaving `at` at `minute`, if it is worth it.
+    fn open_at(&self, at: usize, minute: u32, valve: usize, budget: u32) -> Option<u32> {
+        let open = minute.checked_add(self.dist[at][valve])?.checked_add(1)?;
+        (open < budget).then_some(open)
+    }
+
+    /// The most pressure one agent can release starting from `start`, searching depth first
+    /// and dropping the branches that cannot beat the best plan found so far.
+    fn best_plan(&self, start: &str, budget: u32) -> Result<Plan, SolveError> {
+        let start = self.index_of(start)?;
+        let mut best: (u32, Openings) = (0, Vec::new());
+        let mut openings = Vec::new();
+        self.branch_and_bound(start, 0, budget, 0, 0, &mut openings, &mut best);
+        Ok(self.plan(budget, &best.1))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn branch_and_bound(
+        &self,
+        at: usize,
+        minute: u32,
+        budget: u32,
+        opened: ValveSet,
+        released: u32,
+        openings: &mut Openings,
+        best: &mut (u32, Openings),
+    ) {
+        if released > best.0 {
+            debug!("Better plan releasing {}: {:?}", released, openings);
+            *best = (released, openings.clone());
+        }
+
+        let reachable: Vec<(usize, u32)> = (0..self.useful)
+            .filter(|valve| opened & (1 << valve) == 0)
+            .filter_map(|valve| Some((valve, self.open_at(at, minute, valve, budget)?)))
+            .collect();
+        // each valve opened as soon as possible, as if all the others were on the way
+        let bound: u32 = reachable
+            .iter()
+            .map(|(valve, open)| self.flows[*valve] * (budget - open))
+            .sum();
+        if released + bound <= best.0 {
+            return;
+        }
+
+        for (valve, open) in reachable {
+            openings.push((valve, open));
+            self.branch_and_bound(
+                valve,
+                open,
+                budget,
+                opened | (1 << valve),
+                released + self.flows[valve] * (budget - open),
+                openings,
+                best,
+            );
+            openings.pop();
+        }
+    }
+
+    /// For each set of valves one agent can open starting from `start`, the best order to
+    /// open them in.
+    fn plans_by_set(&self, start: &str, budget: u32) -> Result<PlansBySet, SolveError> {
+        let start = self.index_of(start)?;
+        let mut plans = HashMap::new();
+        let mut openings = Vec::new();
+        self.enumerate(start, 0, budget, 0, 0, &mut openings, &mut plans);
+        Ok(plans)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn enumerate(
+        &self,
+        at: usize,
+        minute: u32,
+        budget: u32,
+        opened: ValveSet,
+        released: u32,
+        openings: &mut Openings,
+        plans: &mut PlansBySet,
+    ) {
+        let entry = plans.entry(opened).or_insert((0, Vec::new()));
+        if released > entry.0 || opened == 0 {
+            *entry = (released, openings.clone());
+        }
+
+        for valve in (0..self.useful).filter(|valve| opened & (1 << valve) == 0) {
+            if let Some(open) = self.open_at(at, minute, valve, budget) {
+                openings.push((valve, open));
+                self.enumerate(
+                    valve,
+                    open,
+                    budget,
+                    opened | (1 << valve),
+                    released + self.flows[valve] * (budget - open),
+                    openings,
+                    plans,
+                );
+                openings.pop();
+            }
+        }
+    }
+
+    /// The most pressure the agents can release together, each one opening its own valves.
+    fn solve(&self, agents: &[Agent]) -> Result<Solution, SolveError> {
+        let mut tables: Vec<Vec<(ValveSet, (u32, Openings))>> = Vec::new();
+        for agent in agents {
+            let mut plans: Vec<(ValveSet, (u32, Openings))> = self
+                .plans_by_set(&agent.start, agent.budget)?
+                .into_iter()
+                .collect();
+            plans.sort_by_key(|(_, (released, _))| Reverse(*released));
+            tables.push(plans);
+        }
+        // the most the agents after each one can add, to prune the combinations
+        let mut rest = vec![0; agents.len() + 1];
+        for k in (0..agents.len()).rev() {
+            rest[k] = rest[k + 1] + tables[k][0].1 .0;
+        }
+
+        let mut chosen = Vec::new();
+        let mut best: (u32, Vec<usize>) = (0, vec![0; agents.len()]);
+        combine(agents, &tables, &rest, 0, 0, &mut chosen, &mut best);
+
+        let plans: Vec<Plan> = agents
+            .iter()
+            .zip(best.1.iter())
+            .enumerate()
+            .map(|(k, (agent, idx))| self.plan(agent.budget, &tables[k][*idx].1 .1))
+            .collect();
+        let solution = Solution {
+            released: plans.iter().map(|p| p.released).sum(),
+            plans,
+        };
+        debug!("Timeline:\n{}", self.timeline(agents, &solution));
+        Ok(solution)
+    }
+
+    /// What the agents do minute by minute, with the flow of the open valves and the pressure
+    /// released so far.
+    fn timeline(&self, agents: &[Agent], solution: &Solution) -> String {
+        let budget = agents.iter().map(|a| a.budget).max().unwrap_or(0);
+        let flow_of = |name: &str| self.flows[self.names.iter().position(|n| n == name).unwrap()];
+
+        let mut header = " min".to_string();
+        for agent in agents {
+            header.push_str(&format!(
+                " | {:<8}",
+                format!("{}:{}", agent.start, agent.budget)
+            ));
+        }
+        header.push_str(" | flow | released");
+        let mut lines = vec![header];
+
+        let mut released = 0;
+        for minute in 1..=budget {
+            let mut line = format!("{:>4}", minute);
+            let mut flow = 0;
+            for (agent, plan) in agents.iter().zip(solution.plans.iter()) {
+                let action = if minute > agent.budget {
+                    String::new()
+                } else {
+                    match plan.schedule.iter().find(|o| o.minute >= minute) {
+                        Some(o) if o.minute == minute => format!("open {}", o.valve),
+                        Some(o) => format!("> {}", o.valve),
+                        None => ".".to_string(),
+                    }
+                };
+                line.push_str(&format!(" | {:<8}", action));
+                flow += plan
+                    .schedule
+                    .iter()
+                    .filter(|o| o.minute < minute && minute <= agent.budget)
+                    .map(|o| flow_of(&o.valve))
+                    .sum::<u32>();
+            }
+            released += flow;
+            line.push_str(&format!(" | {:>4} | {:>8}", flow, released));
+            lines.push(line);
+        }
+        lines.join("\n")
     }
 }
 
-fn println_dbg(msg: &str) {
-    if env::var("DEBUG").is_ok() {
-        println!("{}", msg);
+/// Picks a plan for each agent from `k` on, with no valve opened twice, and keeps the
+/// combination releasing the most pressure in `best`, as indices in the agents' tables.
+fn combine(
+    agents: &[Agent],
+    tables: &[Vec<(ValveSet, (u32, Openings))>],
+    rest: &[u32],
+    used: ValveSet,
+    released: u32,
+    chosen: &mut Vec<usize>,
+    best: &mut (u32, Vec<usize>),
+) {
+    let k = chosen.len();
+    if k == agents.len() {
+        if released > best.0 {
+            *best = (released, chosen.clone());
+        }
+        return;
+    }
+
+    // the plans of identical agents can be swapped, only try them in one order
+    let first = match k {
+        0 => 0,
+        _ if agents[k] == agents[k - 1] => chosen[k - 1],
+        _ => 0,
+    };
+    for (idx, (set, (plan_released, _))) in tables[k].iter().enumerate().skip(first) {
+        // the plans are sorted, the next ones cannot do better
+        if released + plan_released + rest[k + 1] <= best.0 {
+            break;
+        }
+        if set & used != 0 {
+            continue;
+        }
+        chosen.push(idx);
+        combine(
+            agents,
+            tables,
+            rest,
+            used | set,
+            released + plan_released,
+            chosen,
+            best,
+        );
+        chosen.pop();
     }
 }
 
-fn dbg_dbg(data: &dyn Debug) {
-    if env::var("DEBUG").is_ok() {
-        dbg!(data);
+fn parse_system(contents: &str) -> ValveSystem {
+    let mut system = ValveSystem::new();
+    for line in contents.lines() {
+        system.add_valve(line);
     }
+    system
 }
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    part1(&contents);
-    part2(&contents);
+    let plan = part1(&contents).expect("Should have been able to solve part 1");
+    println!("Part 1: {}", plan.released);
+    println!("  {}", plan);
+
+    let solution = part2(&contents).expect("Should have been able to solve part 2");
+    println!("Part 2: {}", solution.released);
+    for plan in solution.plans.iter() {
+        println!("  {}", plan);
+    }
+
+    // e.g. --agents AA:26,AA:26,AA:26 for two helpers
+    let args: Vec<String> = env::args().collect();
+    if let Some(spec) = args.iter().skip_while(|a| *a != "--agents").nth(1) {
+        let agents: Vec<Agent> = spec
+            .split(',')
+            .map(|a| a.parse::<Agent>())
+            .collect::<Result<_, _>>()
+            .expect("Should have been able to read the agents");
+        let solution =
+            solve_for(&contents, &agents).expect("Should have been able to solve for the agents");
+        println!("With {} agents: {}", agents.len(), solution.released);
+        for (agent, plan) in agents.iter().zip(solution.plans.iter()) {
+            println!("  {}:{} {}", agent.start, agent.budget, plan);
+        }
+    }
+}
+
+/// Opens valves alone for 30 minutes.
+fn part1(contents: &String) -> Result<Plan, SolveError> {
+    let system = parse_system(contents);
+    Network::new(&system, &["AA"])?.best_plan("AA", 30)
 }
 
-fn part1(contents: &String) {}
+/// Opens valves with an elephant, after spending 4 of the 30 minutes teaching it.
+fn part2(contents: &String) -> Result<Solution, SolveError> {
+    solve_for(contents, &[Agent::new("AA", 26), Agent::new("AA", 26)])
+}
 
-fn part2(contents: &String) {}
+fn solve_for(contents: &str, agents: &[Agent]) -> Result<Solution, SolveError> {
+    let system = parse_system(contents);
+    let starts: Vec<&str> = agents.iter().map(|a| a.start.as_str()).collect();
+    Network::new(&system, &starts)?.solve(agents)
+}
 
 #[cfg(test)]
 mod tests {
@@ -193,7 +515,7 @@ mod tests {
 
     #[test]
     fn test_parts() {
-        // env::set_var("DEBUG", "true");
+        // debug::configure("d16=trace");
 
         let test_contents = String::from_str(
             "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
@@ -209,7 +531,130 @@ Valve JJ has flow rate=21; tunnel leads to valve II",
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        let plan = part1(&test_contents).unwrap();
+        assert_eq!(plan.released, 1651);
+        let order: Vec<(&str, u32)> = plan
+            .schedule
+            .iter()
+            .map(|o| (o.valve.as_str(), o.minute))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("DD", 2),
+                ("BB", 5),
+                ("JJ", 9),
+                ("HH", 17),
+                ("EE", 21),
+                ("CC", 24)
+            ]
+        );
+
+        let solution = part2(&test_contents).unwrap();
+        assert_eq!(solution.released, 1707);
+        assert_eq!(solution.plans.len(), 2);
+        let opened: HashSet<&str> = solution
+            .plans
+            .iter()
+            .flat_map(|p| p.schedule.iter().map(|o| o.valve.as_str()))
+            .collect();
+        assert_eq!(opened.len(), 6);
+    }
+
+    #[test]
+    fn test_network() {
+        let system = parse_system(
+            "Valve AA has flow rate=0; tunnels lead to valves BB
+Valve BB has flow rate=0; tunnels lead to valves AA, CC
+Valve CC has flow rate=5; tunnels lead to valves BB
+Valve DD has flow rate=7; tunnels lead to valves DD",
+        );
+        assert_eq!(
+            Network::new(&system, &["ZZ"]).err(),
+            Some(SolveError::UnknownValve("ZZ".to_string()))
+        );
+
+        let network = Network::new(&system, &["AA"]).unwrap();
+        assert_eq!(network.names, ["CC", "DD", "AA"]);
+        assert_eq!(network.dist[2][0], 2);
+        assert_eq!(network.dist[2][1], u32::MAX);
+
+        // DD cannot be reached, and CC is open too late to release anything
+        let plan = network.best_plan("AA", 10).unwrap();
+        assert_eq!(plan.released, 5 * 7);
+        assert_eq!(network.best_plan("AA", 3).unwrap(), Plan::default());
+    }
+
+    #[test]
+    fn test_agents() {
+        let test_contents = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+        assert_eq!("AA:26".parse(), Ok(Agent::new("AA", 26)));
+        assert!("AA".parse::<Agent>().is_err());
+        assert!("AA:x".parse::<Agent>().is_err());
+
+        // a single agent finds the same plan as the branch and bound search
+        let solution = solve_for(test_contents, &[Agent::new("AA", 30)]).unwrap();
+        assert_eq!(solution.released, 1651);
+        // a helper with no time does not change anything
+        let solution =
+            solve_for(test_contents, &[Agent::new("AA", 30), Agent::new("AA", 0)]).unwrap();
+        assert_eq!(solution.released, 1651);
+        assert_eq!(solution.plans[1], Plan::default());
+
+        // more helpers never release less, and never open the same valve
+        let agents = vec![Agent::new("AA", 26); 3];
+        let solution = solve_for(test_contents, &agents).unwrap();
+        assert!(solution.released >= 1707);
+        let mut opened: Vec<&str> = solution
+            .plans
+            .iter()
+            .flat_map(|p| p.schedule.iter().map(|o| o.valve.as_str()))
+            .collect();
+        let count = opened.len();
+        opened.sort();
+        opened.dedup();
+        assert_eq!(opened.len(), count);
+
+        // starting next to HH and JJ
+        let agents = [Agent::new("GG", 10), Agent::new("II", 5)];
+        let solution = solve_for(test_contents, &agents).unwrap();
+        assert_eq!(solution.released, 22 * 8 + 20 * 3 + 3 + 21 * 3);
+        assert_eq!(solution.plans[0].to_string(), "HH@2 DD@7 EE@9 (239)");
+        assert_eq!(solution.plans[1].to_string(), "JJ@2 (63)");
+        assert_eq!(
+            solve_for(test_contents, &[Agent::new("ZZ", 10)]).err(),
+            Some(SolveError::UnknownValve("ZZ".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_timeline() {
+        let system = parse_system(
+            "Valve AA has flow rate=0; tunnels lead to valves BB
+Valve BB has flow rate=3; tunnels lead to valves AA",
+        );
+        let network = Network::new(&system, &["AA", "BB"]).unwrap();
+        let agents = [Agent::new("AA", 4), Agent::new("BB", 2)];
+        let solution = network.solve(&agents).unwrap();
+        // BB opened by its neighbour releases for longer
+        assert_eq!(solution.released, 3 * 2);
+        assert_eq!(
+            network.timeline(&agents, &solution),
+            " min | AA:4     | BB:2     | flow | released
+   1 | > BB     | .        |    0 |        0
+   2 | open BB  | .        |    0 |        0
+   3 | .        |          |    3 |        3
+   4 | .        |          |    3 |        6"
+        );
     }
 }
@@ -1,98 +1,484 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
 
 fn main() {
-    part1();
-    part2();
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+
+    // `--rules file` plays with the rules of another game, written like `RPS_RULES`
+    let rules = match env::args().skip_while(|arg| arg != "--rules").nth(1) {
+        Some(path) => {
+            let table = fs::read_to_string(path).expect("Should have been able to read the rules");
+            Rules::parse(&table).expect("Should have been able to load the rules")
+        }
+        None => Rules::rock_paper_scissors(),
+    };
+
+    println!("{}", part1(&contents, &rules).expect("Bad strategy guide"));
+    println!("{}", part2(&contents, &rules).expect("Bad strategy guide"));
+}
+
+/// Plays the shapes of the second column.
+fn part1(contents: &String, rules: &Rules) -> Result<u32, GuideError> {
+    play(contents, rules, Column::Shape)
+}
+
+/// Plays for the outcomes of the second column.
+fn part2(contents: &String, rules: &Rules) -> Result<u32, GuideError> {
+    play(contents, rules, Column::Outcome)
+}
+
+/// The rules of the puzzle: each line gives a shape, its score, and the shapes it defeats. The
+/// last line gives the scores for a loss, a draw and a win.
+const RPS_RULES: &str = "Rock 1 beats Scissors
+Paper 2 beats Rock
+Scissors 3 beats Paper
+outcomes 0 3 6";
+
+/// A shape, as its index in the rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Shape(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RulesError {
+    /// Lines are numbered from 1
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    UnknownShape {
+        line: usize,
+        name: String,
+    },
+    DuplicateShape {
+        line: usize,
+        name: String,
+    },
+    /// Both shapes are said to defeat the other, or a shape to defeat itself
+    Inconsistent {
+        winner: String,
+        loser: String,
+    },
+    NoShape,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Syntax { line, text } => write!(
+                f,
+                "Line {}: expected `<shape> <score> beats <shape>, ...` or `outcomes <loss> <draw> <win>`, found {:?}",
+                line, text
+            ),
+            RulesError::UnknownShape { line, name } => {
+                write!(f, "Line {}: unknown shape {}", line, name)
+            }
+            RulesError::DuplicateShape { line, name } => {
+                write!(f, "Line {}: shape {} is already defined", line, name)
+            }
+            RulesError::Inconsistent { winner, loser } => {
+                write!(f, "{} cannot defeat {}, which defeats it", winner, loser)
+            }
+            RulesError::NoShape => write!(f, "The rules have no shape"),
+        }
+    }
 }
 
-fn score(me: &str, other: &str) -> i32 {
-    let score: i32;
-    match me {
-        "X" => {
-            score = 1;
-            match other {
-                "A" => return score + 3,
-                "B" => return score,
-                "C" => return score + 6,
-                _ => return 0,
+impl Error for RulesError {}
+
+/// The shapes of a game, what each one defeats, and how rounds are scored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    names: Vec<String>,
+    scores: Vec<u32>,
+    /// `beats[a][b]` when shape `a` defeats shape `b`
+    beats: Vec<Vec<bool>>,
+    /// Scores for a loss, a draw and a win
+    outcome_scores: [u32; 3],
+}
+
+impl Rules {
+    fn rock_paper_scissors() -> Self {
+        Rules::parse(RPS_RULES).expect("The rules of the puzzle should be valid")
+    }
+
+    /// Reads a rule table like `RPS_RULES`. Shapes that neither defeat nor are defeated by each
+    /// other draw, and outcomes default to the puzzle scores.
+    fn parse(table: &str) -> Result<Self, RulesError> {
+        let mut names = Vec::new();
+        let mut scores = Vec::new();
+        let mut defeated: Vec<(usize, Vec<String>)> = Vec::new();
+        let mut outcome_scores = [0, 3, 6];
+
+        for (idx, text) in table.lines().enumerate() {
+            let line = idx + 1;
+            let syntax = || RulesError::Syntax {
+                line,
+                text: text.to_string(),
+            };
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            match tokens[..] {
+                [] => (),
+                ["outcomes", loss, draw, win] => {
+                    for (score, value) in outcome_scores.iter_mut().zip([loss, draw, win]) {
+                        *score = value.parse().map_err(|_| syntax())?;
+                    }
+                }
+                ["outcomes", ..] => return Err(syntax()),
+                [name, score, ref rest @ ..] => {
+                    if names.iter().any(|n| n == name) {
+                        return Err(RulesError::DuplicateShape {
+                            line,
+                            name: name.to_string(),
+                        });
+                    }
+                    let losers = match rest {
+                        [] => Vec::new(),
+                        ["beats", ..] => rest[1..]
+                            .join(" ")
+                            .split(',')
+                            .map(|loser| loser.trim().to_string())
+                            .collect(),
+                        _ => return Err(syntax()),
+                    };
+                    names.push(name.to_string());
+                    scores.push(score.parse().map_err(|_| syntax())?);
+                    defeated.push((line, losers));
+                }
+                _ => return Err(syntax()),
             }
         }
-        "Y" => {
-            score = 2;
-            match other {
-                "A" => return score + 6,
-                "B" => return score + 3,
-                "C" => return score,
-                _ => return 0,
+        if names.is_empty() {
+            return Err(RulesError::NoShape);
+        }
+
+        let mut beats = vec![vec![false; names.len()]; names.len()];
+        for (winner, (line, losers)) in defeated.iter().enumerate() {
+            for loser in losers.iter().filter(|l| !l.is_empty()) {
+                let loser = names.iter().position(|n| n == loser).ok_or_else(|| {
+                    RulesError::UnknownShape {
+                        line: *line,
+                        name: loser.to_string(),
+                    }
+                })?;
+                beats[winner][loser] = true;
             }
         }
-        "Z" => {
-            score = 3;
-            match other {
-                "A" => return score,
-                "B" => return score + 6,
-                "C" => return score + 3,
-                _ => return 0,
+        for winner in 0..names.len() {
+            for loser in 0..names.len() {
+                if beats[winner][loser] && (winner == loser || beats[loser][winner]) {
+                    return Err(RulesError::Inconsistent {
+                        winner: names[winner].clone(),
+                        loser: names[loser].clone(),
+                    });
+                }
             }
         }
-        _ => return 0,
+
+        Ok(Rules {
+            names,
+            scores,
+            beats,
+            outcome_scores,
+        })
+    }
+
+    fn outcome(&self, me: Shape, other: Shape) -> Outcome {
+        if self.beats[me.0][other.0] {
+            Outcome::Win
+        } else if self.beats[other.0][me.0] {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    fn score(&self, me: Shape, other: Shape) -> u32 {
+        self.scores[me.0] + self.outcome_scores[self.outcome(me, other) as usize]
+    }
+
+    /// The first shape, in the order of the rules, to play against `other` to get `outcome`.
+    fn shape_for(&self, other: Shape, outcome: Outcome) -> Option<Shape> {
+        (0..self.names.len())
+            .map(Shape)
+            .find(|me| self.outcome(*me, other) == outcome)
     }
 }
 
-fn compute_play(other: &str, instr: &str) -> &'static str {
-    match other {
-        "A" => match instr {
-            "X" => return "Z",
-            "Y" => return "X",
-            "Z" => return "Y",
-            _ => return "",
-        },
-        "B" => match instr {
-            "X" => return "X",
-            "Y" => return "Y",
-            "Z" => return "Z",
-            _ => return "",
-        },
-        "C" => match instr {
-            "X" => return "Y",
-            "Y" => return "Z",
-            "Z" => return "X",
-            _ => return "",
-        },
-        _ => return "",
+/// How to read the second column of the strategy guide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    /// The shape to play, `X`, `Y` and `Z` for three shapes, more letters before `X` for more
+    Shape,
+    /// The outcome to get: `X` to lose, `Y` to draw and `Z` to win
+    Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GuideError {
+    /// Lines are numbered from 1
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    UnknownLetter {
+        line: usize,
+        letter: char,
+    },
+    NoShapeFor {
+        line: usize,
+        outcome: Outcome,
+    },
+}
+
+impl fmt::Display for GuideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuideError::Syntax { line, text } => {
+                write!(f, "Line {}: expected two letters, found {:?}", line, text)
+            }
+            GuideError::UnknownLetter { line, letter } => {
+                write!(f, "Line {}: unknown letter {}", line, letter)
+            }
+            GuideError::NoShapeFor { line, outcome } => {
+                write!(f, "Line {}: no shape gives a {:?}", line, outcome)
+            }
+        }
     }
 }
 
-fn part1() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+impl Error for GuideError {}
+
+/// Reads the rounds of the strategy guide, as the shapes played by the opponent and by me.
+fn parse_guide(
+    contents: &str,
+    rules: &Rules,
+    column: Column,
+) -> Result<Vec<(Shape, Shape)>, GuideError> {
+    let nb_shapes = rules.names.len() as u32;
+    let letter_index = |letter: char, first: char, count: u32| {
+        (first..='Z').take(count as usize).position(|l| l == letter)
+    };
 
-    let mut total = 0;
-    for line in contents.split("\n") {
-        if line.len() == 0 {
+    let mut rounds = Vec::new();
+    for (idx, text) in contents.lines().enumerate() {
+        let line = idx + 1;
+        if text.is_empty() {
             continue;
         }
-        let mut played = line.split(" ");
-        let other = played.next().unwrap();
-        let me = played.next().unwrap();
-        total += score(&me, &other);
+        let letters: Vec<char> = text.chars().collect();
+        let (other, mine) = match letters[..] {
+            [other, ' ', mine] => (other, mine),
+            _ => {
+                return Err(GuideError::Syntax {
+                    line,
+                    text: text.to_string(),
+                })
+            }
+        };
+        let unknown = |letter| GuideError::UnknownLetter { line, letter };
+
+        let other = letter_index(other, 'A', nb_shapes)
+            .map(Shape)
+            .ok_or(unknown(other))?;
+        let me = match column {
+            Column::Shape => {
+                let first = char::from_u32('Z' as u32 + 1 - nb_shapes.min(26)).unwrap_or('A');
+                letter_index(mine, first, nb_shapes)
+                    .map(Shape)
+                    .ok_or(unknown(mine))?
+            }
+            Column::Outcome => {
+                let outcome = match mine {
+                    'X' => Outcome::Loss,
+                    'Y' => Outcome::Draw,
+                    'Z' => Outcome::Win,
+                    _ => return Err(unknown(mine)),
+                };
+                rules
+                    .shape_for(other, outcome)
+                    .ok_or(GuideError::NoShapeFor { line, outcome })?
+            }
+        };
+        rounds.push((other, me));
     }
+    Ok(rounds)
+}
 
-    println!("{total}");
+/// My total score when following the strategy guide.
+fn play(contents: &str, rules: &Rules, column: Column) -> Result<u32, GuideError> {
+    Ok(parse_guide(contents, rules, column)?
+        .iter()
+        .map(|(other, me)| rules.score(*me, *other))
+        .sum())
 }
 
-fn part2() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut total = 0;
-    for line in contents.split("\n") {
-        if line.len() == 0 {
-            continue;
+    const EXAMPLE: &str = "A Y
+B X
+C Z
+";
+
+    #[test]
+    fn test_parts() {
+        let rules = Rules::rock_paper_scissors();
+        assert_eq!(part1(&EXAMPLE.to_string(), &rules), Ok(15));
+        assert_eq!(part2(&EXAMPLE.to_string(), &rules), Ok(12));
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = Rules::rock_paper_scissors();
+        let (rock, paper, scissors) = (Shape(0), Shape(1), Shape(2));
+        assert_eq!(rules.outcome(paper, rock), Outcome::Win);
+        assert_eq!(rules.outcome(rock, paper), Outcome::Loss);
+        assert_eq!(rules.outcome(scissors, scissors), Outcome::Draw);
+        assert_eq!(rules.score(scissors, paper), 3 + 6);
+        assert_eq!(rules.shape_for(rock, Outcome::Loss), Some(scissors));
+
+        // with custom scores
+        let rules = Rules::parse(&RPS_RULES.replace("outcomes 0 3 6", "outcomes 1 2 10")).unwrap();
+        assert_eq!(rules.score(scissors, paper), 3 + 10);
+        assert_eq!(
+            play(EXAMPLE, &rules, Column::Shape),
+            Ok(2 + 10 + 1 + 1 + 3 + 2)
+        );
+    }
+
+    #[test]
+    fn test_spock() {
+        let rules = Rules::parse(
+            "Rock 1 beats Scissors, Lizard
+Paper 2 beats Rock, Spock
+Scissors 3 beats Paper, Lizard
+Lizard 4 beats Spock, Paper
+Spock 5 beats Scissors, Rock",
+        )
+        .unwrap();
+        let (rock, lizard, spock) = (Shape(0), Shape(3), Shape(4));
+        assert_eq!(rules.outcome(lizard, spock), Outcome::Win);
+        assert_eq!(rules.outcome(rock, spock), Outcome::Loss);
+        // Paper and Spock both beat Rock, the first one is picked
+        assert_eq!(rules.shape_for(rock, Outcome::Win), Some(Shape(1)));
+
+        // the second column goes from V to Z
+        assert_eq!(
+            parse_guide("A V\nE Z\nD X", &rules, Column::Shape),
+            Ok(vec![(rock, rock), (spock, spock), (lizard, Shape(2))])
+        );
+        // Rock loses to Spock, and Spock to Lizard
+        assert_eq!(play("E V\nD Z", &rules, Column::Shape), Ok(1 + 5));
+        assert_eq!(
+            parse_guide("F X", &rules, Column::Shape),
+            Err(GuideError::UnknownLetter {
+                line: 1,
+                letter: 'F'
+            })
+        );
+    }
+
+    #[test]
+    fn test_guide_errors() {
+        let rules = Rules::rock_paper_scissors();
+        for column in [Column::Shape, Column::Outcome] {
+            assert_eq!(
+                parse_guide("A Y\nD X", &rules, column),
+                Err(GuideError::UnknownLetter {
+                    line: 2,
+                    letter: 'D'
+                })
+            );
+            assert_eq!(
+                parse_guide("A Y\n\nB W", &rules, column),
+                Err(GuideError::UnknownLetter {
+                    line: 3,
+                    letter: 'W'
+                })
+            );
+            assert_eq!(
+                parse_guide("AY", &rules, column),
+                Err(GuideError::Syntax {
+                    line: 1,
+                    text: "AY".to_string()
+                })
+            );
         }
-        let mut played = line.split(" ");
-        let other = played.next().unwrap();
-        let me = played.next().unwrap();
-        total += score(compute_play(&other, &me), &other);
+
+        // nothing loses against a shape that defeats nothing
+        let rules = Rules::parse("Rock 1\nPaper 2 beats Rock").unwrap();
+        assert_eq!(
+            parse_guide("A X", &rules, Column::Outcome),
+            Err(GuideError::NoShapeFor {
+                line: 1,
+                outcome: Outcome::Loss
+            })
+        );
     }
 
-    println!("{total}");
+    #[test]
+    fn test_rules_errors() {
+        assert_eq!(
+            Rules::parse("Rock 1 beats Scissors\nPaper 2 beats Rock"),
+            Err(RulesError::UnknownShape {
+                line: 1,
+                name: "Scissors".to_string()
+            })
+        );
+        assert_eq!(
+            Rules::parse("Rock 1 beats Paper\nPaper 2 beats Rock"),
+            Err(RulesError::Inconsistent {
+                winner: "Rock".to_string(),
+                loser: "Paper".to_string()
+            })
+        );
+        assert_eq!(
+            Rules::parse("Rock one"),
+            Err(RulesError::Syntax {
+                line: 1,
+                text: "Rock one".to_string()
+            })
+        );
+        assert_eq!(
+            Rules::parse("Rock 1 eats Paper").unwrap_err().to_string(),
+            "Line 1: expected `<shape> <score> beats <shape>, ...` or `outcomes <loss> <draw> <win>`, found \"Rock 1 eats Paper\""
+        );
+        assert_eq!(Rules::parse("outcomes 0 3 6"), Err(RulesError::NoShape));
+
+        // an outcomes line is never read as a shape
+        for outcomes in ["outcomes 0", "outcomes 0 3", "outcomes 0 3 6 9", "outcomes"] {
+            let table = format!("Rock 1\n{}", outcomes);
+            assert_eq!(
+                Rules::parse(&table),
+                Err(RulesError::Syntax {
+                    line: 2,
+                    text: outcomes.to_string()
+                }),
+                "{}",
+                outcomes
+            );
+        }
+
+        assert_eq!(
+            Rules::parse("Rock 1\nPaper 2 beats Rock\nRock 2"),
+            Err(RulesError::DuplicateShape {
+                line: 3,
+                name: "Rock".to_string()
+            })
+        );
+        assert_eq!(
+            Rules::parse("Rock 1\nRock 2").unwrap_err().to_string(),
+            "Line 2: shape Rock is already defined"
+        );
+    }
 }
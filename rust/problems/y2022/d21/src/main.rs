@@ -1,27 +1,8 @@
 use std::collections::HashMap;
-use std::env;
-use std::fmt::Debug;
 use std::fs;
 use std::str::FromStr;
 use std::vec::Vec;
-
-fn print_dbg(msg: &str) {
-    if env::var("DEBUG").is_ok() {
-        print!("{}", msg);
-    }
-}
-
-fn println_dbg(msg: &str) {
-    if env::var("DEBUG").is_ok() {
-        println!("{}", msg);
-    }
-}
-
-fn dbg_dbg(data: &dyn Debug) {
-    if env::var("DEBUG").is_ok() {
-        dbg!(data);
-    }
-}
+use utils::debug;
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
@@ -31,7 +12,7 @@ fn main() {
 }
 
 fn part1(contents: &String) {
-    println_dbg(contents);
+    debug!("{}", contents);
 }
 
 fn part2(contents: &String) {}
@@ -42,7 +23,7 @@ mod tests {
 
     #[test]
     fn test_parts() {
-        env::set_var("DEBUG", "true");
+        debug::configure("trace");
 
         let test_contents = String::from_str(
             "    [D]    
@@ -1,77 +1,180 @@
+use std::error::Error;
+use std::fmt;
 use std::fs;
 
 fn main() {
-    part1();
-    part2();
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+
+    println!(
+        "{}",
+        part1(&contents).expect("Should have been able to sort the rucksacks")
+    );
+    println!(
+        "{}",
+        part2(&contents, 3).expect("Should have been able to sort the rucksacks")
+    );
 }
 
-fn score(letter: char) -> u32 {
-    let ascii = letter as u32;
-    if ascii < 97 {
-        ascii - 65 + 27
-    } else {
-        ascii - 97 + 1
+/// Priority of an item type, 1 to 26 for `a` to `z` and 27 to 52 for `A` to `Z`.
+fn priority(item: char) -> Option<u32> {
+    match item {
+        'a'..='z' => Some(item as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(item as u32 - 'A' as u32 + 27),
+        _ => None,
     }
 }
 
-fn part1() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
-    // let contents = "vJrwpWtwJgWrhcsFMMfFFhFp
-    // jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
-    // PmmdzqPrVvPwwTWBwg
-    // wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
-    // ttgJtRGJQctTZtZT
-    // CrZsJsPPZsGzwwsLwLmpwMDw";
-
-    let mut res: u32 = 0;
-
-    for line in contents.split("\n") {
-        let one = &line[..line.len() / 2];
-        let two = &line[(line.len() / 2)..];
-
-        for letter in one.chars() {
-            if two.contains(&letter.to_string()) {
-                let s = score(letter);
-                println!("{} - {} - {}", line, letter, s);
-                res += s;
-                break;
-            }
+fn item_of(priority: u32) -> char {
+    match priority {
+        1..=26 => (b'a' + (priority - 1) as u8) as char,
+        _ => (b'A' + (priority - 27) as u8) as char,
+    }
+}
+
+/// A set of item types, with bit `priority - 1` set for each type in the set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet((1 << 52) - 1);
+
+    /// The types of the items listed in `items`, or the first character that is not an item.
+    fn parse(items: &str) -> Result<Self, char> {
+        items.chars().try_fold(ItemSet::default(), |set, item| {
+            let priority = priority(item).ok_or(item)?;
+            Ok(ItemSet(set.0 | 1 << (priority - 1)))
+        })
+    }
+
+    fn intersection(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    /// The types found in all the sets, none if there is no set.
+    fn common<I: IntoIterator<Item = ItemSet>>(sets: I) -> ItemSet {
+        let mut sets = sets.into_iter().peekable();
+        if sets.peek().is_none() {
+            return ItemSet::default();
         }
+        sets.fold(ItemSet::ALL, ItemSet::intersection)
     }
 
-    println!("{res}");
+    fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// The priorities of the types in the set, in increasing order.
+    fn priorities(self) -> impl Iterator<Item = u32> {
+        (1..=52).filter(move |p| self.0 & 1 << (p - 1) != 0)
+    }
 }
 
-fn part2() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
-    // let contents = "vJrwpWtwJgWrhcsFMMfFFhFp
-    // jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
-    // PmmdzqPrVvPwwTWBwg
-    // wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
-    // ttgJtRGJQctTZtZT
-    // CrZsJsPPZsGzwwsLwLmpwMDw";
-    let mut res: u32 = 0;
-    let mut it = contents.split("\n");
-    let mut one = it.next().unwrap();
-    let mut two = it.next().unwrap();
-    let mut three = it.next().unwrap();
-    loop {
-        for letter in one.chars() {
-            if two.contains(&letter.to_string()) && three.contains(&letter.to_string()) {
-                let s = score(letter);
-                res += s;
-                break;
+impl fmt::Display for ItemSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.priorities()
+            .try_for_each(|p| write!(f, "{}", item_of(p)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RucksackError {
+    /// Lines are numbered from 1
+    BadItem { line: usize, item: char },
+    /// A rucksack with an odd number of items cannot be split in two compartments
+    OddSize { line: usize },
+    /// Rucksacks expected to share exactly one item type, starting at `line`
+    NotOneCommon { line: usize, common: ItemSet },
+    /// The last group, starting at `line`, has fewer rucksacks than the others
+    IncompleteGroup {
+        line: usize,
+        size: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for RucksackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RucksackError::BadItem { line, item } => {
+                write!(f, "Line {}: {:?} is not an item", line, item)
             }
+            RucksackError::OddSize { line } => {
+                write!(f, "Line {}: odd number of items", line)
+            }
+            RucksackError::NotOneCommon { line, common } => write!(
+                f,
+                "Line {}: expected a single common item, found {:?}",
+                line,
+                common.to_string()
+            ),
+            RucksackError::IncompleteGroup { line, size, found } => write!(
+                f,
+                "Line {}: expected a group of {} rucksacks, found {}",
+                line, size, found
+            ),
         }
-        one = it.next().unwrap_or("");
-        if one == "" {
-            break;
-        }
-        two = it.next().unwrap();
-        three = it.next().unwrap();
     }
+}
+
+impl Error for RucksackError {}
+
+/// The rucksacks listed in `contents`, with their line numbers. Blank lines are skipped.
+fn rucksacks(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn parse_items(line: usize, items: &str) -> Result<ItemSet, RucksackError> {
+    ItemSet::parse(items).map_err(|item| RucksackError::BadItem { line, item })
+}
+
+/// The priority of the only item type found in all of `sets`, the first one being at `line`.
+fn single_common(line: usize, sets: &[ItemSet]) -> Result<u32, RucksackError> {
+    let common = ItemSet::common(sets.iter().copied());
+    match common.priorities().next() {
+        Some(priority) if common.len() == 1 => Ok(priority),
+        _ => Err(RucksackError::NotOneCommon { line, common }),
+    }
+}
+
+/// Sums the priorities of the item types found in both compartments of each rucksack.
+fn part1(contents: &String) -> Result<u32, RucksackError> {
+    rucksacks(contents)
+        .map(|(line, items)| {
+            if items.len() % 2 != 0 {
+                return Err(RucksackError::OddSize { line });
+            }
+            let (one, two) = items.split_at(items.len() / 2);
+            single_common(line, &[parse_items(line, one)?, parse_items(line, two)?])
+        })
+        .sum()
+}
+
+/// Sums the priorities of the badges, the item types shared by each group of `group_size`
+/// rucksacks.
+fn part2(contents: &String, group_size: usize) -> Result<u32, RucksackError> {
+    assert!(group_size > 0, "Groups hold at least one rucksack");
+    let sets = rucksacks(contents)
+        .map(|(line, items)| Ok((line, parse_items(line, items)?)))
+        .collect::<Result<Vec<(usize, ItemSet)>, RucksackError>>()?;
 
-    println!("{res}");
+    sets.chunks(group_size)
+        .map(|group| {
+            let line = group[0].0;
+            if group.len() < group_size {
+                return Err(RucksackError::IncompleteGroup {
+                    line,
+                    size: group_size,
+                    found: group.len(),
+                });
+            }
+            let sets: Vec<ItemSet> = group.iter().map(|(_, set)| *set).collect();
+            single_common(line, &sets)
+        })
+        .sum()
 }
 
 fn add(a: i32, b: i32) -> i32 {
@@ -100,6 +203,87 @@ mod tests {
         println!("{}", 'a' as u32);
     }
 
+    const EXAMPLE: &str = "vJrwpWtwJgWrhcsFMMfFFhFp
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+PmmdzqPrVvPwwTWBwg
+wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+ttgJtRGJQctTZtZT
+CrZsJsPPZsGzwwsLwLmpwMDw
+";
+
+    #[test]
+    fn test_parts() {
+        let contents = EXAMPLE.to_string();
+        assert_eq!(part1(&contents), Ok(157));
+        assert_eq!(part2(&contents, 3), Ok(70));
+        // any group size
+        let contents = "abX\ncdX\nefY\nghY".to_string();
+        assert_eq!(part2(&contents, 2), Ok(50 + 51));
+        assert_eq!(
+            part2(&"xaYb\nb".to_string(), 1),
+            Err(RucksackError::NotOneCommon {
+                line: 1,
+                common: ItemSet::parse("abxY").unwrap()
+            })
+        );
+        assert_eq!(part2(&"aa\nb".to_string(), 1), Ok(1 + 2));
+    }
+
+    #[test]
+    fn test_item_set() {
+        assert_eq!(priority('a'), Some(1));
+        assert_eq!(priority('z'), Some(26));
+        assert_eq!(priority('A'), Some(27));
+        assert_eq!(priority('Z'), Some(52));
+        assert_eq!(priority('1'), None);
+        assert!((1..=52).all(|p| priority(item_of(p)) == Some(p)));
+
+        let set = ItemSet::parse("ZzaZa").unwrap();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.to_string(), "azZ");
+        assert_eq!(set.priorities().collect::<Vec<u32>>(), [1, 26, 52]);
+        assert_eq!(ItemSet::parse("ab-c"), Err('-'));
+        assert_eq!(ItemSet::parse("").unwrap().len(), 0);
+
+        let sets = ["abcX", "bcXY", "cXZ"].map(|s| ItemSet::parse(s).unwrap());
+        assert_eq!(ItemSet::common(sets).to_string(), "cX");
+        assert_eq!(ItemSet::common([]), ItemSet::default());
+        assert_eq!(ItemSet::common([ItemSet::ALL]).len(), 52);
+    }
+
+    #[test]
+    fn test_errors() {
+        let contents = "aX\nbX\ncX\n".to_string();
+        assert_eq!(
+            part2(&contents, 2),
+            Err(RucksackError::IncompleteGroup {
+                line: 3,
+                size: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            part2(&contents, 5).unwrap_err().to_string(),
+            "Line 1: expected a group of 5 rucksacks, found 3"
+        );
+        assert_eq!(
+            part1(&"aa\nabc".to_string()),
+            Err(RucksackError::OddSize { line: 2 })
+        );
+        assert_eq!(
+            part1(&"aa\n\na b ".to_string()),
+            Err(RucksackError::BadItem { line: 3, item: ' ' })
+        );
+        assert_eq!(
+            part1(&"abab".to_string()),
+            Err(RucksackError::NotOneCommon {
+                line: 1,
+                common: ItemSet::parse("ab").unwrap()
+            })
+        );
+        assert_eq!(part2(&"".to_string(), 3), Ok(0));
+    }
+
     #[test]
     fn test_ascii() {
         println!("{}", 'a' as u32);
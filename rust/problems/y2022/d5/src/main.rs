@@ -1,136 +1,303 @@
-use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::str::FromStr;
 use std::vec::Vec;
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
-    let lines: Vec<&str> = contents.split("\n").collect();
+    // `--steps` draws the stacks after each move
+    let show_steps = env::args().any(|arg| arg == "--steps");
 
-    part1(&lines);
-    part2(&lines);
+    println!(
+        "{}",
+        part1(&contents, show_steps).expect("Could not move the crates")
+    );
+    println!(
+        "{}",
+        part2(&contents, show_steps).expect("Could not move the crates")
+    );
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Instr {
-    qty: i32,
+    qty: usize,
     from: usize,
     to: usize,
 }
 
-fn part1(lines: &Vec<&str>) {
-    let (crate_lines, instr_lines) = parse_input(lines);
-    let mut stacks = parse_crate_lines(crate_lines);
-    let instrs = parse_instr_lines(instr_lines);
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.qty, self.from, self.to)
+    }
+}
+
+impl FromStr for Instr {
+    type Err = String;
 
-    for instr in instrs {
-        for _step in 0..instr.qty {
-            let c = stacks.get_mut(&instr.from).unwrap().pop().unwrap();
-            stacks.get_mut(&instr.to).unwrap().push(c);
+    /// Reads `move 1 from 2 to 1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split(' ').collect();
+        match tokens[..] {
+            ["move", qty, "from", from, "to", to] => {
+                let number = |token: &str| token.parse::<usize>().map_err(|e| e.to_string());
+                Ok(Instr {
+                    qty: number(qty)?,
+                    from: number(from)?,
+                    to: number(to)?,
+                })
+            }
+            _ => Err("expected `move <qty> from <stack> to <stack>`".to_string()),
         }
     }
+}
 
-    for i in 1..=stacks.len() {
-        let stack = stacks.get(&i).unwrap();
-        print!("{}", stack[stack.len() - 1]);
-    }
-    println!("");
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    /// The drawing does not end with the line of stack numbers
+    MissingLabels,
+    /// The stack numbers are not 1, 2, 3...
+    BadLabels(String),
+    /// Lines are numbered from 1 in the whole input
+    BadCrate { line: usize, text: String },
+    BadInstr {
+        line: usize,
+        text: String,
+        reason: String,
+    },
 }
 
-fn print_stacks(stacks: &HashMap<usize, Vec<char>>) {
-    for (k, v) in stacks {
-        println!("Stack {k}: {v:?}");
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingLabels => write!(f, "The drawing has no line of stack numbers"),
+            ParseError::BadLabels(text) => write!(f, "Bad stack numbers {:?}", text),
+            ParseError::BadCrate { line, text } => {
+                write!(f, "Line {}: bad row of crates {:?}", line, text)
+            }
+            ParseError::BadInstr { line, text, reason } => {
+                write!(f, "Line {}: bad move {:?}, {}", line, text, reason)
+            }
+        }
     }
-    println!("==============");
 }
 
-fn parse_input<'a, 'b>(lines: &'a Vec<&'b str>) -> (&'a [&'b str], &'a [&'b str]) {
-    let mut crate_lines_end = 0;
-    for line in lines.iter() {
-        if line.chars().nth(1).unwrap() == '1' {
-            break;
-        } else {
-            crate_lines_end += 1;
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MoveError {
+    UnknownStack { instr: Instr, stack: usize },
+    NotEnoughCrates { instr: Instr, found: usize },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::UnknownStack { instr, stack } => {
+                write!(f, "Cannot {}: there is no stack {}", instr, stack)
+            }
+            MoveError::NotEnoughCrates { instr, found } => write!(
+                f,
+                "Cannot {}: stack {} only holds {} crates",
+                instr, instr.from, found
+            ),
         }
     }
-    (
-        &lines.as_slice()[..crate_lines_end],
-        &lines.as_slice()[crate_lines_end + 2..],
-    )
 }
 
-fn parse_crate_lines(crate_lines: &[&str]) -> HashMap<usize, Vec<char>> {
-    let mut stacks: HashMap<usize, Vec<char>> = HashMap::new();
-    let ignored_chars = ['[', ']', ' '];
+impl Error for MoveError {}
+
+/// A model of crane, telling how the crates it lifts land on the other stack.
+trait Crane {
+    fn name(&self) -> &'static str;
 
-    let base = &crate_lines[0];
-    let nb_stacks = (base.len() + 1) / 4;
-    for s in 1..=nb_stacks {
-        stacks.insert(s, Vec::new());
+    /// Orders `crates`, lifted from the top of a stack and given from bottom to top, as they
+    /// end up on the other stack, from bottom to top.
+    fn arrange(&self, crates: &mut [char]);
+}
+
+/// Moves the crates one at a time, so they land in reverse order.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> &'static str {
+        "CrateMover 9000"
     }
 
-    for idx in (0..(crate_lines.len())).rev() {
-        let line = crate_lines[idx];
-        for (i, c) in line.chars().enumerate() {
-            if ignored_chars.contains(&c) {
-                continue;
-            }
-            let stack_nb = (i - 1) / 4 + 1;
-            stacks.get_mut(&stack_nb).unwrap().push(c);
-        }
+    fn arrange(&self, crates: &mut [char]) {
+        crates.reverse();
     }
-    stacks
 }
 
-fn parse_instr_lines(instr_lines: &[&str]) -> Vec<Instr> {
-    let mut instrs = Vec::new();
+/// Moves all the crates at once, so they keep their order.
+struct CrateMover9001;
 
-    for line in instr_lines {
-        if line.len() == 0 {
-            continue;
-        }
-        let tokens: Vec<&str> = line.split(" ").collect();
-        instrs.push(Instr {
-            qty: FromStr::from_str(tokens[1]).unwrap(),
-            from: FromStr::from_str(tokens[3]).unwrap(),
-            to: FromStr::from_str(tokens[5]).unwrap(),
-        });
+impl Crane for CrateMover9001 {
+    fn name(&self) -> &'static str {
+        "CrateMover 9001"
     }
 
-    instrs
+    fn arrange(&self, _crates: &mut [char]) {}
 }
 
-fn part2(lines: &Vec<&str>) {
-    let (crate_lines, instr_lines) = parse_input(lines);
-    let mut stacks = parse_crate_lines(crate_lines);
-    let instrs = parse_instr_lines(instr_lines);
+/// Stacks of crates, numbered from 1 like in the drawing, each one from bottom to top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stacks {
+    stacks: Vec<Vec<char>>,
+}
 
-    for instr in instrs {
-        let mut buffer = Vec::new();
-        for _step in 0..instr.qty {
-            let c = stacks.get_mut(&instr.from).unwrap().pop().unwrap();
-            buffer.push(c);
+impl Stacks {
+    /// Reads the drawing, the crates above a line of stack numbers. `first_line` is the number
+    /// of the first line of the drawing, for the errors.
+    fn parse(drawing: &str, first_line: usize) -> Result<Self, ParseError> {
+        let lines: Vec<&str> = drawing.lines().collect();
+        let (labels, rows) = lines.split_last().ok_or(ParseError::MissingLabels)?;
+        if labels.contains('[') {
+            return Err(ParseError::MissingLabels);
         }
-        for _step in 0..instr.qty {
-            let c = buffer.pop().unwrap();
-            stacks.get_mut(&instr.to).unwrap().push(c);
+        let nb_stacks = labels.split_whitespace().count();
+        let expected = (1..=nb_stacks).map(|n| n.to_string());
+        if nb_stacks == 0 || !labels.split_whitespace().eq(expected) {
+            return Err(ParseError::BadLabels(labels.to_string()));
+        }
+
+        let mut stacks = vec![Vec::new(); nb_stacks];
+        for (idx, row) in rows.iter().enumerate().rev() {
+            let bad_crate = || ParseError::BadCrate {
+                line: first_line + idx,
+                text: row.to_string(),
+            };
+            let chars: Vec<char> = row.chars().collect();
+            // editors may drop the trailing spaces
+            if chars.len() > 4 * nb_stacks - 1 {
+                return Err(bad_crate());
+            }
+            for (stack, cell) in chars.chunks(4).enumerate() {
+                match cell {
+                    ['[', c, ']'] | ['[', c, ']', ' '] if c.is_ascii_alphabetic() => {
+                        if stacks[stack].len() != rows.len() - 1 - idx {
+                            // a crate above nothing
+                            return Err(bad_crate());
+                        }
+                        stacks[stack].push(*c);
+                    }
+                    cell if cell.iter().all(|c| *c == ' ') => (),
+                    _ => return Err(bad_crate()),
+                }
+            }
+        }
+        Ok(Stacks { stacks })
+    }
+
+    /// The crate on top of each stack, a space for empty stacks.
+    fn tops(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|stack| *stack.last().unwrap_or(&' '))
+            .collect()
+    }
+
+    fn apply(&mut self, crane: &dyn Crane, instr: &Instr) -> Result<(), MoveError> {
+        for stack in [instr.from, instr.to] {
+            if stack == 0 || stack > self.stacks.len() {
+                return Err(MoveError::UnknownStack {
+                    instr: *instr,
+                    stack,
+                });
+            }
+        }
+        let from = &mut self.stacks[instr.from - 1];
+        if from.len() < instr.qty {
+            return Err(MoveError::NotEnoughCrates {
+                instr: *instr,
+                found: from.len(),
+            });
+        }
+        if instr.from == instr.to {
+            // whatever the crane, the crates land where they were
+            return Ok(());
+        }
+
+        let mut crates = from.split_off(from.len() - instr.qty);
+        crane.arrange(&mut crates);
+        self.stacks[instr.to - 1].extend(crates);
+        Ok(())
+    }
+}
+
+impl fmt::Display for Stacks {
+    /// Draws the stacks like the puzzle, with the line of stack numbers.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let row: Vec<String> = self
+                .stacks
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("[{}]", c),
+                    None => "   ".to_string(),
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
         }
+        let labels: Vec<String> = (1..=self.stacks.len())
+            .map(|n| format!("{:^3}", n))
+            .collect();
+        write!(f, "{}", labels.join(" "))
     }
+}
+
+/// Splits the input in the drawing of the stacks and the moves, after the blank line.
+fn parse_input(contents: &str) -> Result<(Stacks, Vec<Instr>), ParseError> {
+    let (drawing, moves) = contents.split_once("\n\n").unwrap_or((contents, ""));
+    let stacks = Stacks::parse(drawing, 1)?;
 
-    for i in 1..=stacks.len() {
-        let stack = stacks.get(&i).unwrap();
-        print!("{}", stack[stack.len() - 1]);
+    let first_move_line = drawing.lines().count() + 2;
+    let instrs = moves
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(idx, line)| {
+            line.parse::<Instr>()
+                .map_err(|reason| ParseError::BadInstr {
+                    line: first_move_line + idx,
+                    text: line.to_string(),
+                    reason,
+                })
+        })
+        .collect::<Result<Vec<Instr>, ParseError>>()?;
+    Ok((stacks, instrs))
+}
+
+/// Moves the crates with `crane`, and gives the crates on top of the stacks at the end.
+fn operate(contents: &str, crane: &dyn Crane, show_steps: bool) -> Result<String, Box<dyn Error>> {
+    let (mut stacks, instrs) = parse_input(contents)?;
+    if show_steps {
+        println!("== {} ==\n{}\n", crane.name(), stacks);
+    }
+    for instr in instrs.iter() {
+        stacks.apply(crane, instr)?;
+        if show_steps {
+            println!("{}\n{}\n", instr, stacks);
+        }
     }
-    println!("");
+    Ok(stacks.tops())
+}
+
+fn part1(contents: &String, show_steps: bool) -> Result<String, Box<dyn Error>> {
+    operate(contents, &CrateMover9000, show_steps)
+}
+
+fn part2(contents: &String, show_steps: bool) -> Result<String, Box<dyn Error>> {
+    operate(contents, &CrateMover9001, show_steps)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part1() {
-        let contents = "    [D]    
+    const EXAMPLE: &str = "    [D]    
 [N] [C]    
 [Z] [M] [P]
  1   2   3 
@@ -140,23 +307,112 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2";
 
-        let lines: Vec<&str> = contents.split("\n").collect();
-        part1(&lines);
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(&EXAMPLE.to_string(), false).unwrap(), "CMZ");
     }
 
     #[test]
     fn test_part2() {
-        let contents = "    [D]    
-[N] [C]    
-[Z] [M] [P]
- 1   2   3 
+        assert_eq!(part2(&EXAMPLE.to_string(), false).unwrap(), "MCD");
+    }
 
-move 1 from 2 to 1
-move 3 from 1 to 3
-move 2 from 2 to 1
-move 1 from 1 to 2";
+    #[test]
+    fn test_drawing() {
+        let (drawing, _) = EXAMPLE.split_once("\n\n").unwrap();
+        let (mut stacks, instrs) = parse_input(EXAMPLE).unwrap();
+        assert_eq!(
+            stacks.stacks,
+            [vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]
+        );
+        assert_eq!(stacks.to_string(), drawing);
+        assert_eq!(Stacks::parse(&stacks.to_string(), 1), Ok(stacks.clone()));
+
+        // the first step of the puzzle
+        stacks.apply(&CrateMover9000, &instrs[0]).unwrap();
+        assert_eq!(
+            stacks.to_string(),
+            "[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+        );
+
+        // trailing spaces are optional, and empty stacks are drawn
+        let stacks = Stacks::parse("[A]\n[B]         [C]\n 1   2   3   4", 1).unwrap();
+        assert_eq!(stacks.tops(), "A  C");
+        assert_eq!(
+            stacks.to_string(),
+            "[A]            \n[B]         [C]\n 1   2   3   4 "
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_input("[A] [B]\n\nmove 1 from 1 to 2"),
+            Err(ParseError::MissingLabels)
+        );
+        assert_eq!(
+            parse_input("[A] [B]\n 1   3 "),
+            Err(ParseError::BadLabels(" 1   3 ".to_string()))
+        );
+        assert_eq!(
+            parse_input("[A] (B)\n 1   2 "),
+            Err(ParseError::BadCrate {
+                line: 1,
+                text: "[A] (B)".to_string()
+            })
+        );
+        // a crate floating above an empty stack
+        assert_eq!(
+            parse_input("    [A]\n[B]    \n 1   2 "),
+            Err(ParseError::BadCrate {
+                line: 1,
+                text: "    [A]".to_string()
+            })
+        );
+        assert_eq!(
+            parse_input("[A]\n 1 \n\nmove 1 from 1 to 1\nmove one from 1 to 1"),
+            Err(ParseError::BadInstr {
+                line: 5,
+                text: "move one from 1 to 1".to_string(),
+                reason: "invalid digit found in string".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_move_errors() {
+        let (mut stacks, _) = parse_input(EXAMPLE).unwrap();
+        let instr = Instr {
+            qty: 3,
+            from: 1,
+            to: 2,
+        };
+        assert_eq!(
+            stacks.apply(&CrateMover9001, &instr),
+            Err(MoveError::NotEnoughCrates { instr, found: 2 })
+        );
+        let instr = Instr {
+            qty: 1,
+            from: 1,
+            to: 4,
+        };
+        assert_eq!(
+            stacks.apply(&CrateMover9001, &instr),
+            Err(MoveError::UnknownStack { instr, stack: 4 })
+        );
+        assert_eq!(
+            MoveError::UnknownStack { instr, stack: 4 }.to_string(),
+            "Cannot move 1 from 1 to 4: there is no stack 4"
+        );
+
+        // moving onto the same stack changes nothing, with both cranes
+        let before = stacks.clone();
+        let instr = "move 2 from 2 to 2".parse::<Instr>().unwrap();
+        stacks.apply(&CrateMover9000, &instr).unwrap();
+        stacks.apply(&CrateMover9001, &instr).unwrap();
+        assert_eq!(stacks, before);
 
-        let lines: Vec<&str> = contents.split("\n").collect();
-        part2(&lines);
+        let contents = "[A]\n 1 \n\nmove 2 from 1 to 1".to_string();
+        assert!(part1(&contents, false).is_err());
     }
 }
@@ -1,77 +1,169 @@
 // COMMUNICATION SYSTEM
-use std::collections::HashMap;
-use std::collections::VecDeque;
+use std::env;
 use std::fs;
-use std::str::FromStr;
+use std::io::{self, Read};
 use std::vec::Vec;
 
 fn main() {
+    // `--stdin` reads the datastream from the standard input instead of input.txt, in one pass
+    if env::args().any(|arg| arg == "--stdin") {
+        let stdin = io::stdin().lock();
+        let bytes = stdin
+            .bytes()
+            .map(|byte| byte.expect("Should have been able to read stdin"));
+        let markers = find_markers(bytes, &[4, 14]);
+        print_marker(markers[0]);
+        print_marker(markers[1]);
+        return;
+    }
+
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    part1(&contents);
-    part2(&contents);
+    print_marker(part1(&contents));
+    print_marker(part2(&contents));
+}
+
+fn print_marker(marker: Option<usize>) {
+    match marker {
+        Some(position) => println!("{}", position),
+        None => println!("No marker found"),
+    }
+}
+
+fn part1(contents: &String) -> Option<usize> {
+    find_marker(contents.bytes(), 4)
 }
 
-fn part1(contents: &String) {
-    println!("{}", find_unique_window(contents, 4));
+fn part2(contents: &String) -> Option<usize> {
+    find_marker(contents.bytes(), 14)
 }
 
-fn is_unique_window(window: &str) -> bool {
-    let mut unique = HashMap::new();
-    for elem in window.chars() {
-        if unique.contains_key(&elem) {
-            return false;
-        } else {
-            unique.insert(elem, true);
+/// Looks for the first `length` bytes in a row that are all different, one byte at a time.
+///
+/// The window of different bytes ending at the last one pushed is tracked with the position
+/// each byte was last seen at: when a byte comes back inside the window, the window restarts
+/// just after its previous occurrence.
+struct MarkerDetector {
+    length: usize,
+    last_seen: [Option<usize>; 256],
+    /// Start of the window, and position of the next byte
+    start: usize,
+    position: usize,
+    found: Option<usize>,
+}
+
+impl MarkerDetector {
+    fn new(length: usize) -> Self {
+        MarkerDetector {
+            length,
+            last_seen: [None; 256],
+            start: 0,
+            position: 0,
+            found: if length == 0 { Some(0) } else { None },
         }
     }
-    return true;
+
+    /// Reads the next byte, and gives the number of bytes read up to the end of the marker once
+    /// it has been found.
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        if self.found.is_some() {
+            return self.found;
+        }
+
+        if let Some(seen) = self.last_seen[byte as usize] {
+            if seen >= self.start {
+                self.start = seen + 1;
+            }
+        }
+        self.last_seen[byte as usize] = Some(self.position);
+        self.position += 1;
+
+        if self.position - self.start == self.length {
+            self.found = Some(self.position);
+        }
+        self.found
+    }
 }
 
-fn part2(contents: &String) {
-    println!("{}", find_unique_window(contents, 14));
+/// Number of bytes read up to the end of the first marker of `length` different bytes.
+fn find_marker<I: IntoIterator<Item = u8>>(stream: I, length: usize) -> Option<usize> {
+    let mut detector = MarkerDetector::new(length);
+    if detector.found.is_some() {
+        return detector.found;
+    }
+    stream.into_iter().find_map(|byte| detector.push(byte))
 }
 
-fn find_unique_window(data: &String, length: usize) -> usize {
-    for i in 0..(data.len() - length) {
-        let window = &data[i..(i + length)];
-        if is_unique_window(window) {
-            return i + length;
+/// Looks for markers of several lengths in a single pass, stopping once they are all found.
+fn find_markers<I: IntoIterator<Item = u8>>(stream: I, lengths: &[usize]) -> Vec<Option<usize>> {
+    let mut detectors: Vec<MarkerDetector> =
+        lengths.iter().map(|l| MarkerDetector::new(*l)).collect();
+    let mut stream = stream.into_iter();
+    while detectors.iter().any(|d| d.found.is_none()) {
+        let Some(byte) = stream.next() else {
+            break;
+        };
+        for detector in detectors.iter_mut() {
+            detector.push(byte);
         }
     }
-    return usize::MAX;
+    detectors.iter().map(|d| d.found).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_parts() {
-        let test_contents = String::from_str("mjqjpqmgbljsphdztnvjfqwrcgsmlb").unwrap();
-        println!("{}", test_contents);
-        part1(&test_contents);
-        part2(&test_contents);
-        println!("-----");
-        let test_contents = String::from_str("bvwbjplbgvbhsrlpgdmjqwftvncz").unwrap();
-        println!("{}", test_contents);
-        part1(&test_contents);
-        part2(&test_contents);
-        println!("-----");
-        let test_contents = String::from_str("nppdvjthqldpwncqszvftbrmjlhg").unwrap();
-        println!("{}", test_contents);
-        part1(&test_contents);
-        part2(&test_contents);
-        println!("-----");
-        let test_contents = String::from_str("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg").unwrap();
-        println!("{}", test_contents);
-        part1(&test_contents);
-        part2(&test_contents);
-        println!("-----");
-        let test_contents = String::from_str("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw").unwrap();
-        println!("{}", test_contents);
-        part1(&test_contents);
-        part2(&test_contents);
-        println!("-----");
+        let examples = [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+            ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+        ];
+        for (stream, start_of_packet, start_of_message) in examples {
+            let test_contents = String::from_str(stream).unwrap();
+            assert_eq!(part1(&test_contents), Some(start_of_packet), "{}", stream);
+            assert_eq!(part2(&test_contents), Some(start_of_message), "{}", stream);
+            assert_eq!(
+                find_markers(stream.bytes(), &[4, 14]),
+                [Some(start_of_packet), Some(start_of_message)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_marker() {
+        // too short, or never different enough
+        assert_eq!(find_marker("abc".bytes(), 4), None);
+        assert_eq!(find_marker("abababab".bytes(), 3), None);
+        assert_eq!(find_marker("".bytes(), 1), None);
+
+        // any length
+        assert_eq!(find_marker("aab".bytes(), 0), Some(0));
+        assert_eq!(find_marker("aab".bytes(), 1), Some(1));
+        assert_eq!(find_marker("aab".bytes(), 2), Some(3));
+        assert_eq!(find_marker("abcd".bytes(), 4), Some(4));
+
+        // bytes that are not ASCII are just bytes
+        assert_eq!(find_marker("é".bytes(), 2), Some(2));
+        assert_eq!(find_marker([0, 255, 0, 1, 255].into_iter(), 3), Some(4));
+
+        // the stream is not read past the marker
+        let mut stream = "abcabcdxyz".bytes();
+        assert_eq!(find_marker(&mut stream, 4), Some(7));
+        assert_eq!(stream.collect::<Vec<u8>>(), b"xyz");
+    }
+
+    #[test]
+    fn test_find_markers() {
+        assert_eq!(
+            find_markers("aabcdd".bytes(), &[2, 4, 5]),
+            [Some(3), Some(5), None]
+        );
+        assert_eq!(find_markers("".bytes(), &[0]), [Some(0)]);
     }
 }
@@ -1,207 +1,537 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::rc::{Rc, Weak};
-use std::str::FromStr;
 use std::vec::Vec;
+use utils::debug;
 
-#[derive(Debug, Default)]
-struct File {
+type NodeId = usize;
+
+#[derive(Debug)]
+enum NodeKind {
+    File,
+    Dir(BTreeMap<String, NodeId>),
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    kind: NodeKind,
+    // size of the file, or total size of everything below the folder
     size: usize,
 }
 
-#[derive(Debug, Default)]
-struct Folder<'a> {
-    name: &'a str,
-    folders: RefCell<HashMap<&'a str, Rc<Folder<'a>>>>,
-    files: RefCell<HashMap<&'a str, File>>,
-    parent: Weak<Folder<'a>>,
-    size: RefCell<usize>,
+/// Files and folders stored in an arena, indexed by `NodeId`. Folder sizes are kept up to date
+/// when files are added, so reading them is free.
+#[derive(Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
 }
 
-impl<'a> Folder<'a> {
-    fn build_root() -> Self {
-        Folder::with_name_and_parent(&"/", Weak::new())
+impl FileSystem {
+    const ROOT: NodeId = 0;
+
+    fn new() -> Self {
+        FileSystem {
+            nodes: vec![Node {
+                name: "/".to_string(),
+                parent: None,
+                kind: NodeKind::Dir(BTreeMap::new()),
+                size: 0,
+            }],
+        }
+    }
+
+    fn name(&self, id: NodeId) -> &str {
+        &self.nodes[id].name
+    }
+
+    fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    fn size(&self, id: NodeId) -> usize {
+        self.nodes[id].size
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir(_))
+    }
+
+    fn children(&self, id: NodeId) -> impl DoubleEndedIterator<Item = NodeId> + '_ {
+        let children = match &self.nodes[id].kind {
+            NodeKind::Dir(children) => Some(children.values().copied()),
+            NodeKind::File => None,
+        };
+        children.into_iter().flatten()
+    }
+
+    /// The children of a folder, as they would be listed by `ls`, sorted.
+    fn entries(&self, id: NodeId) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .children(id)
+            .map(|child| match self.is_dir(child) {
+                true => Entry::Dir(self.name(child).to_string()),
+                false => Entry::File(self.name(child).to_string(), self.size(child)),
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[id].kind {
+            NodeKind::Dir(children) => children.get(name).copied(),
+            NodeKind::File => None,
+        }
+    }
+
+    fn add(&mut self, parent: NodeId, name: &str, kind: NodeKind, size: usize) -> NodeId {
+        if let Some(existing) = self.child(parent, name) {
+            return existing;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+            size,
+        });
+        match &mut self.nodes[parent].kind {
+            NodeKind::Dir(children) => children.insert(name.to_string(), id),
+            NodeKind::File => panic!("{} is not a folder", self.path(parent)),
+        };
+
+        let mut ancestor = Some(parent);
+        while let Some(folder) = ancestor {
+            self.nodes[folder].size += size;
+            ancestor = self.parent(folder);
+        }
+        id
+    }
+
+    /// Adds a folder to `parent`, or returns the one already there.
+    fn add_dir(&mut self, parent: NodeId, name: &str) -> NodeId {
+        self.add(parent, name, NodeKind::Dir(BTreeMap::new()), 0)
+    }
+
+    /// Adds a file to `parent`, or returns the one already there.
+    fn add_file(&mut self, parent: NodeId, name: &str, size: usize) -> NodeId {
+        self.add(parent, name, NodeKind::File, size)
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        match self.parent(id) {
+            None => "/".to_string(),
+            Some(Self::ROOT) => format!("/{}", self.name(id)),
+            Some(parent) => format!("{}/{}", self.path(parent), self.name(id)),
+        }
+    }
+
+    /// Finds the node at an absolute path such as `/a/e`.
+    fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.strip_prefix('/')?
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(Self::ROOT, |id, name| self.child(id, name))
+    }
+
+    /// Nodes below `from` (included) with their depth relative to it, parents first.
+    fn pre_order(&self, from: NodeId) -> PreOrder<'_> {
+        PreOrder {
+            fs: self,
+            stack: vec![(from, 0)],
+        }
+    }
+
+    /// Nodes below `from` (included) with their depth relative to it, children first.
+    fn post_order(&self, from: NodeId) -> PostOrder<'_> {
+        PostOrder {
+            fs: self,
+            stack: vec![(from, 0, false)],
+        }
     }
 
-    fn with_name_and_parent(name: &'a str, parent: Weak<Folder<'a>>) -> Self {
-        Folder {
-            name,
-            folders: RefCell::new(HashMap::new()),
-            files: RefCell::new(HashMap::new()),
-            parent,
-            size: RefCell::new(0),
+    fn find(&self) -> Find<'_> {
+        Find {
+            fs: self,
+            dirs: None,
+            min_size: 0,
+            max_size: usize::MAX,
+            max_depth: usize::MAX,
         }
     }
+
+    /// Draws the nodes below `from` like the puzzle does.
+    fn tree(&self, from: NodeId) -> String {
+        self.pre_order(from)
+            .map(|(id, depth)| {
+                let kind = if self.is_dir(id) { "dir" } else { "file" };
+                format!(
+                    "{}- {} ({}, size={})\n",
+                    "  ".repeat(depth),
+                    self.name(id),
+                    kind,
+                    self.size(id)
+                )
+            })
+            .collect()
+    }
+
+    /// Lists the total size of the folders below `from`, like `du`.
+    fn du(&self, from: NodeId) -> String {
+        self.post_order(from)
+            .filter(|(id, _)| self.is_dir(*id))
+            .map(|(id, _)| format!("{}\t{}\n", self.size(id), self.path(id)))
+            .collect()
+    }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tree(Self::ROOT))
+    }
+}
 
-    part1(&contents);
-    part2(&contents);
+struct PreOrder<'f> {
+    fs: &'f FileSystem,
+    stack: Vec<(NodeId, usize)>,
 }
 
-fn part1(contents: &String) {
-    let root = parse_fs_output(contents);
-    dbg!(root.clone());
-    println!("45872605 too high");
-    println!("{}", bounded_part1_size(root.clone()));
+impl Iterator for PreOrder<'_> {
+    type Item = (NodeId, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, depth) = self.stack.pop()?;
+        // reversed, so that children come out in name order
+        self.stack
+            .extend(self.fs.children(id).rev().map(|child| (child, depth + 1)));
+        Some((id, depth))
+    }
 }
 
-fn part2(contents: &String) {
-    let root = parse_fs_output(contents);
-    let used_space = total_size(root.clone());
-    println!("Used space {}", used_space);
-    let needed = 30000000 - (70000000 - used_space);
-    println!("Needed {}", needed);
-    let f = part2_smallest_sub_dir_bigger_than_needed(root.clone(), needed).unwrap();
-    println!("Folder {}, of size {}", f.name, total_size(f.clone()));
-}
-
-fn part2_smallest_sub_dir_bigger_than_needed(
-    folder: Rc<Folder>,
-    needed: usize,
-) -> Option<Rc<Folder>> {
-    let mut min_folder = folder.clone();
-    if total_size(min_folder.clone()) < needed {
-        return None;
-    } else {
-        for (_key, child) in (*folder.folders.borrow()).iter() {
-            match part2_smallest_sub_dir_bigger_than_needed(child.clone(), needed) {
-                None => continue,
-                Some(small_child) => {
-                    if total_size(small_child.clone()) < total_size(min_folder.clone()) {
-                        min_folder = small_child.clone();
-                    }
-                }
+struct PostOrder<'f> {
+    fs: &'f FileSystem,
+    // the flag tells whether the children of the node are already on the stack
+    stack: Vec<(NodeId, usize, bool)>,
+}
+
+impl Iterator for PostOrder<'_> {
+    type Item = (NodeId, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (id, depth, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some((id, depth));
             }
+            self.stack.push((id, depth, true));
+            self.stack.extend(
+                self.fs
+                    .children(id)
+                    .rev()
+                    .map(|child| (child, depth + 1, false)),
+            );
         }
     }
+}
 
-    return Some(min_folder);
+/// A `find`-like query on the nodes of a `FileSystem`. All the bounds are inclusive, and the
+/// depth of the root is 0.
+struct Find<'f> {
+    fs: &'f FileSystem,
+    // Some(true) for folders only, Some(false) for files only
+    dirs: Option<bool>,
+    min_size: usize,
+    max_size: usize,
+    max_depth: usize,
 }
 
-fn bounded_part1_size(folder: Rc<Folder>) -> usize {
-    let mut size = 0;
-    for (_key, child) in (*folder.folders.borrow()).iter() {
-        size += bounded_part1_size(child.clone())
+impl<'f> Find<'f> {
+    fn dirs(mut self) -> Self {
+        self.dirs = Some(true);
+        self
     }
-    let fsize = total_size(folder);
-    if fsize < 100000 {
-        size += fsize;
+
+    fn files(mut self) -> Self {
+        self.dirs = Some(false);
+        self
     }
-    size
-}
 
-fn total_size(folder: Rc<Folder>) -> usize {
-    let mut size = 0;
-    for (_key, child) in (*folder.folders.borrow()).iter() {
-        size += total_size(child.clone());
+    fn min_size(mut self, size: usize) -> Self {
+        self.min_size = size;
+        self
     }
-    for (_key, file) in (*folder.files.borrow()).iter() {
-        size += file.size;
+
+    fn max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
     }
-    println!("Tot size of {}: {}", folder.name, size);
-    size
-}
 
-fn parse_fs_output(data: &String) -> Rc<Folder> {
-    let fs_root = Rc::new(Folder::build_root());
-    let mut cur_folder = fs_root.clone();
-    for line in data.split("\n") {
-        cur_folder = interpret_line(line, cur_folder);
+    fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
     }
 
-    return fs_root;
+    fn iter(&self) -> impl Iterator<Item = NodeId> + 'f {
+        let fs = self.fs;
+        let dirs = self.dirs;
+        let sizes = self.min_size..=self.max_size;
+        let max_depth = self.max_depth;
+        fs.pre_order(FileSystem::ROOT)
+            .filter(move |(id, depth)| {
+                *depth <= max_depth
+                    && sizes.contains(&fs.size(*id))
+                    && dirs.is_none_or(|dirs| dirs == fs.is_dir(*id))
+            })
+            .map(|(id, _)| id)
+    }
 }
 
-fn find_root<'a>(folder: Rc<Folder<'a>>) -> Rc<Folder<'a>> {
-    match folder.parent.upgrade() {
-        Some(parent) => find_root(parent.clone()),
-        None => folder.clone(),
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+
+    part1(&contents).expect("Could not read the transcript");
+    part2(&contents).expect("Could not read the transcript");
+
+    // `--tree /a/e` draws the folder at that path, and `--files 2` lists the files at most 2
+    // folders deep
+    let arg = |name: &str| env::args().skip_while(|arg| arg != name).nth(1);
+    if arg("--tree").is_some() || arg("--files").is_some() {
+        let fs = parse_fs_output(&contents).expect("Could not read the transcript");
+        if let Some(path) = arg("--tree") {
+            match fs.lookup(&path) {
+                Some(id) => print!("{}", fs.tree(id)),
+                None => println!("Nothing at {}", path),
+            }
+        }
+        if let Some(depth) = arg("--files") {
+            let depth = depth.parse().expect("Expected --files <depth>");
+            for id in fs.find().files().max_depth(depth).iter() {
+                println!("{}\t{}", fs.size(id), fs.path(id));
+            }
+        }
     }
 }
 
-fn interpret_line<'a>(line: &'a str, current_folder: Rc<Folder<'a>>) -> Rc<Folder<'a>> {
-    if line.len() == 0 {
-        return current_folder;
+fn part1(contents: &String) -> Result<usize, TranscriptError> {
+    let fs = parse_fs_output(contents)?;
+    debug!("File system:\n{}", fs);
+    println!("45872605 too high");
+    let size = fs
+        .find()
+        .dirs()
+        .max_size(100000)
+        .iter()
+        .map(|id| fs.size(id))
+        .sum();
+    println!("{}", size);
+    Ok(size)
+}
+
+const DISK_SPACE: usize = 70000000;
+const UPDATE_SPACE: usize = 30000000;
+
+/// The size of the smallest folder to delete to make room for the update, 0 if there is
+/// already enough room, or `None` if no folder is big enough.
+fn part2(contents: &String) -> Result<Option<usize>, TranscriptError> {
+    let fs = parse_fs_output(contents)?;
+    debug!("Folder sizes:\n{}", fs.du(FileSystem::ROOT));
+    let used_space = fs.size(FileSystem::ROOT);
+    println!("Used space {}", used_space);
+    let needed = UPDATE_SPACE.saturating_sub(DISK_SPACE.saturating_sub(used_space));
+    println!("Needed {}", needed);
+    if needed == 0 {
+        return Ok(Some(0));
+    }
+    let folder = fs
+        .find()
+        .dirs()
+        .min_size(needed)
+        .iter()
+        .min_by_key(|id| fs.size(*id));
+    match folder {
+        Some(f) => println!("Folder {}, of size {}", fs.path(f), fs.size(f)),
+        None => println!("No folder is big enough"),
     }
+    Ok(folder.map(|f| fs.size(f)))
+}
 
-    let tokens = line.split(" ").collect::<Vec<&str>>();
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CdTarget {
+    Root,
+    Up,
+    Down(String),
+}
 
-    match (tokens[0], tokens[1]) {
-        ("$", "cd") => match tokens[2] {
-            "/" => find_root(current_folder),
-            ".." => current_folder.parent.upgrade().unwrap(),
-            dirname => move_down_to(current_folder.clone(), dirname),
-        },
-        ("$", "ls") => current_folder,
-        ("dir", dirname) => {
-            add_child_folder(current_folder.clone(), dirname);
-            current_folder
-        }
-        (size, filename) => {
-            add_child_file(
-                current_folder.clone(),
-                filename,
-                size.parse::<usize>().unwrap(),
-            );
-            current_folder
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Entry {
+    Dir(String),
+    File(String, usize),
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        match self {
+            Entry::Dir(name) | Entry::File(name, _) => name,
         }
     }
 }
 
-fn add_child_file<'a>(folder: Rc<Folder<'a>>, child: &'a str, size: usize) -> Rc<Folder<'a>> {
-    *folder.size.borrow_mut() += size;
-    folder.files.borrow_mut().insert(child, File { size });
-    folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Cd(CdTarget),
+    /// With the entries listed in its output
+    Ls(Vec<Entry>),
 }
 
-fn add_child_folder<'a>(folder: Rc<Folder<'a>>, child: &'a str) -> Rc<Folder<'a>> {
-    folder.folders.borrow_mut().insert(
-        child,
-        Rc::new(Folder::with_name_and_parent(child, Rc::downgrade(&folder))),
-    );
-    folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TranscriptError {
+    /// A line that is neither a known command nor an `ls` output
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    UnknownDir {
+        line: usize,
+        name: String,
+    },
+    AboveRoot {
+        line: usize,
+    },
+    /// A folder listed twice with different contents, or an entry listed twice in a folder
+    Conflict {
+        line: usize,
+        path: String,
+    },
 }
 
-fn move_down_to<'a>(folder: Rc<Folder<'a>>, child_name: &'a str) -> Rc<Folder<'a>> {
-    folder.folders.borrow().get(child_name).unwrap().clone()
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::Syntax { line, text } => {
+                write!(f, "Line {}: cannot read '{}'", line, text)
+            }
+            TranscriptError::UnknownDir { line, name } => {
+                write!(f, "Line {}: cd into {}, which was never listed", line, name)
+            }
+            TranscriptError::AboveRoot { line } => write!(f, "Line {}: cd above /", line),
+            TranscriptError::Conflict { line, path } => {
+                write!(f, "Line {}: listing of {} does not match", line, path)
+            }
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Error for TranscriptError {}
 
-    #[test]
-    fn test_folder() {
-        let root = Rc::new(Folder::build_root());
-        let sub = Rc::new(Folder::with_name_and_parent("sub", Rc::downgrade(&root)));
-        let subsub = Rc::new(Folder::with_name_and_parent("subsub", Rc::downgrade(&sub)));
-        let subsubsub = Rc::new(Folder::with_name_and_parent(
-            "subsubsub",
-            Rc::downgrade(&subsub),
-        ));
+/// Reads the commands of a transcript, each with its line number (starting at 1).
+fn parse_transcript(data: &str) -> Result<Vec<(usize, Command)>, TranscriptError> {
+    let mut commands: Vec<(usize, Command)> = Vec::new();
+    for (idx, text) in data.lines().enumerate() {
+        let line = idx + 1;
+        let syntax_error = || TranscriptError::Syntax {
+            line,
+            text: text.to_string(),
+        };
+        let tokens = text.split_whitespace().collect::<Vec<&str>>();
 
-        assert_eq!(find_root(subsubsub.clone()).name, root.name);
-        dbg!(find_root(sub.clone()));
+        let entry = match tokens[..] {
+            [] => continue,
+            ["$", "cd", "/"] => {
+                commands.push((line, Command::Cd(CdTarget::Root)));
+                continue;
+            }
+            ["$", "cd", ".."] => {
+                commands.push((line, Command::Cd(CdTarget::Up)));
+                continue;
+            }
+            ["$", "cd", name] => {
+                commands.push((line, Command::Cd(CdTarget::Down(name.to_string()))));
+                continue;
+            }
+            ["$", "ls"] => {
+                commands.push((line, Command::Ls(Vec::new())));
+                continue;
+            }
+            ["dir", name] => Entry::Dir(name.to_string()),
+            [size, name] => {
+                Entry::File(name.to_string(), size.parse().map_err(|_| syntax_error())?)
+            }
+            _ => return Err(syntax_error()),
+        };
+        match commands.last_mut() {
+            Some((_, Command::Ls(entries))) => entries.push(entry),
+            _ => return Err(syntax_error()),
+        }
     }
+    Ok(commands)
+}
 
-    #[test]
-    fn test_add_child_file() {
-        let root = Rc::new(Folder::build_root());
-        add_child_file(root.clone(), &"foo", 123);
-        add_child_file(root.clone(), &"bar", 876);
+/// Replays the commands of a transcript. Listing a folder again is fine, as long as the output
+/// is the same.
+fn build_fs(commands: &[(usize, Command)]) -> Result<FileSystem, TranscriptError> {
+    let mut fs = FileSystem::new();
+    let mut listed: HashSet<NodeId> = HashSet::new();
+    let mut cwd = FileSystem::ROOT;
 
-        assert_eq!(*root.size.borrow(), 999);
-        dbg!(root);
+    for (line, command) in commands {
+        let line = *line;
+        match command {
+            Command::Cd(CdTarget::Root) => cwd = FileSystem::ROOT,
+            Command::Cd(CdTarget::Up) => {
+                cwd = fs.parent(cwd).ok_or(TranscriptError::AboveRoot { line })?;
+            }
+            Command::Cd(CdTarget::Down(name)) => {
+                cwd = fs.child(cwd, name).filter(|id| fs.is_dir(*id)).ok_or(
+                    TranscriptError::UnknownDir {
+                        line,
+                        name: name.clone(),
+                    },
+                )?;
+            }
+            Command::Ls(entries) => {
+                let mut entries = entries.clone();
+                entries.sort();
+                entries.dedup();
+                let conflict = || TranscriptError::Conflict {
+                    line,
+                    path: fs.path(cwd),
+                };
+                if entries.windows(2).any(|w| w[0].name() == w[1].name()) {
+                    return Err(conflict());
+                }
+
+                if !listed.insert(cwd) {
+                    if entries != fs.entries(cwd) {
+                        return Err(conflict());
+                    }
+                    continue;
+                }
+                for entry in entries {
+                    match entry {
+                        Entry::Dir(name) => fs.add_dir(cwd, &name),
+                        Entry::File(name, size) => fs.add_file(cwd, &name, size),
+                    };
+                }
+            }
+        }
     }
+    Ok(fs)
+}
 
-    #[test]
-    fn test_parts() {
-        let test_contents = String::from_str(
-            "$ cd /
+fn parse_fs_output(data: &str) -> Result<FileSystem, TranscriptError> {
+    build_fs(&parse_transcript(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const EXAMPLE: &str = "$ cd /
 $ ls
 dir a
 14848514 b.txt
@@ -223,11 +553,170 @@ $ ls
 4060174 j
 8033020 d.log
 5626152 d.ext
-7214296 k",
-        )
-        .unwrap();
+7214296 k";
+
+    #[test]
+    fn test_add_file() {
+        let mut fs = FileSystem::new();
+        let sub = fs.add_dir(FileSystem::ROOT, "sub");
+        let subsub = fs.add_dir(sub, "subsub");
+        fs.add_file(subsub, "foo", 123);
+        fs.add_file(sub, "bar", 876);
+        // already there, not counted twice
+        fs.add_file(sub, "bar", 876);
+        assert_eq!(fs.add_dir(FileSystem::ROOT, "sub"), sub);
+
+        assert_eq!(fs.size(subsub), 123);
+        assert_eq!(fs.size(sub), 999);
+        assert_eq!(fs.size(FileSystem::ROOT), 999);
+        assert_eq!(fs.path(subsub), "/sub/subsub");
+    }
+
+    #[test]
+    fn test_lookup_and_walk() {
+        let fs = parse_fs_output(EXAMPLE).unwrap();
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(fs.size(e), 584);
+        assert_eq!(fs.lookup("/"), Some(FileSystem::ROOT));
+        assert_eq!(fs.lookup("/d/k").map(|k| fs.size(k)), Some(7214296));
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.lookup("a"), None);
+
+        let names = |walk: &mut dyn Iterator<Item = (NodeId, usize)>| {
+            walk.map(|(id, _)| fs.name(id).to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        let a = fs.lookup("/a").unwrap();
+        assert_eq!(names(&mut fs.pre_order(a)), "a e i f g h.lst");
+        assert_eq!(names(&mut fs.post_order(a)), "i e f g h.lst a");
+
+        assert_eq!(
+            fs.du(FileSystem::ROOT),
+            "584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/\n"
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let fs = parse_fs_output(EXAMPLE).unwrap();
+        let paths = |find: Find| find.iter().map(|id| fs.path(id)).collect::<Vec<String>>();
+
+        assert_eq!(paths(fs.find().dirs().max_size(100000)), ["/a", "/a/e"]);
+        assert_eq!(
+            paths(fs.find().files().min_size(8000000)),
+            ["/b.txt", "/c.dat", "/d/d.log"]
+        );
+        assert_eq!(paths(fs.find().dirs().max_depth(1)), ["/", "/a", "/d"]);
+    }
+
+    #[test]
+    fn test_tree() {
+        let fs = parse_fs_output(EXAMPLE).unwrap();
+        assert_eq!(
+            fs.to_string(),
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+    }
+
+    #[test]
+    fn test_parts() {
+        let test_contents = String::from_str(EXAMPLE).unwrap();
+
+        assert_eq!(part1(&test_contents), Ok(95437));
+        assert_eq!(part2(&test_contents), Ok(Some(24933642)));
 
-        part1(&test_contents);
-        part2(&test_contents);
+        // already enough room
+        assert_eq!(part2(&"$ cd /\n$ ls\n100 a".to_string()), Ok(Some(0)));
+        assert_eq!(part2(&"$ cd /".to_string()), Ok(Some(0)));
+        // more used than the disk holds
+        let full = "$ cd /\n$ ls\ndir a\n5000000 b\n$ cd a\n$ ls\n75000000 c";
+        assert_eq!(part2(&full.to_string()), Ok(Some(75000000)));
+    }
+
+    #[test]
+    fn test_parse_transcript() {
+        let commands = parse_transcript("$ cd /\n$ ls\ndir a\n12 b.txt\n$ cd a\n$ cd ..").unwrap();
+        assert_eq!(
+            commands,
+            [
+                (1, Command::Cd(CdTarget::Root)),
+                (
+                    2,
+                    Command::Ls(vec![
+                        Entry::Dir("a".to_string()),
+                        Entry::File("b.txt".to_string(), 12)
+                    ])
+                ),
+                (5, Command::Cd(CdTarget::Down("a".to_string()))),
+                (6, Command::Cd(CdTarget::Up)),
+            ]
+        );
+
+        for (transcript, line) in [
+            ("$ cd /\n$ rm -rf a", 2),
+            ("$ cd /\ndir a", 2),
+            ("$ ls\nbig b.txt", 2),
+        ] {
+            assert!(matches!(
+                parse_transcript(transcript),
+                Err(TranscriptError::Syntax { line: l, .. }) if l == line
+            ));
+        }
+    }
+
+    #[test]
+    fn test_inconsistent_transcripts() {
+        assert_eq!(
+            parse_fs_output("$ cd /\n$ ls\n12 a\n$ cd a").unwrap_err(),
+            TranscriptError::UnknownDir {
+                line: 4,
+                name: "a".to_string()
+            }
+        );
+        assert_eq!(
+            parse_fs_output("$ ls\ndir a\n$ cd a\n$ cd ..\n$ cd ..").unwrap_err(),
+            TranscriptError::AboveRoot { line: 5 }
+        );
+        assert_eq!(
+            parse_fs_output("$ ls\ndir a\n$ cd a\n$ ls\n1 x\n$ cd ..\n$ cd a\n$ ls\n2 x")
+                .unwrap_err(),
+            TranscriptError::Conflict {
+                line: 8,
+                path: "/a".to_string()
+            }
+        );
+        assert_eq!(
+            parse_fs_output("$ ls\ndir a\n1 a").unwrap_err(),
+            TranscriptError::Conflict {
+                line: 1,
+                path: "/".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_repeated_ls() {
+        let twice = format!("{}\n$ cd /\n$ ls\n8504156 c.dat\ndir d\ndir a\n14848514 b.txt\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d", EXAMPLE);
+        let fs = parse_fs_output(&twice).unwrap();
+        assert_eq!(fs.size(FileSystem::ROOT), 48381165);
+        assert_eq!(
+            fs.to_string(),
+            parse_fs_output(EXAMPLE).unwrap().to_string()
+        );
     }
 }
@@ -1,15 +1,14 @@
-use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::ops::Index;
-use std::str::FromStr;
 use std::vec::Vec;
 
 fn main() {
     let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
 
-    part1(&contents);
-    part2(&contents);
-    part2_with_iter(&contents);
+    let visible = part1(&contents).expect("Should have been able to read the trees");
+    println!("Trees visible: {}", visible);
+    let best_score = part2(&contents).expect("Should have been able to read the trees");
+    println!("Best score {}", best_score);
 }
 
 struct Grid {
@@ -25,13 +24,99 @@ impl Index<usize> for Grid {
 }
 
 impl Grid {
-    fn iter<'a>(&'a self) -> GridIter<'a> {
+    /// Reads one line of digits per row of trees. All the rows must have the same length.
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut grid: Vec<Vec<u32>> = Vec::new();
+        for (line_idx, line) in contents.lines().enumerate() {
+            let trees = line
+                .chars()
+                .map(|tree| {
+                    tree.to_digit(10).ok_or_else(|| {
+                        format!("Line {}: {:?} is not a tree height", line_idx + 1, tree)
+                    })
+                })
+                .collect::<Result<Vec<u32>, String>>()?;
+            if let Some(first) = grid.first() {
+                if trees.len() != first.len() {
+                    return Err(format!(
+                        "Line {}: expected {} trees, found {}",
+                        line_idx + 1,
+                        first.len(),
+                        trees.len()
+                    ));
+                }
+            }
+            grid.push(trees);
+        }
+        Ok(Grid { grid })
+    }
+
+    fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn width(&self) -> usize {
+        self.grid.first().map_or(0, |line| line.len())
+    }
+
+    /// All the trees, row by row, with their line and column.
+    fn iter(&self) -> GridIter<'_> {
         GridIter {
             data: self,
             line_idx: 0,
             col_idx: 0,
         }
     }
+
+    /// Finds which trees can be seen from outside the grid and the scenic score of each one.
+    ///
+    /// Each row and column is walked both ways with a stack of the trees that can still block
+    /// the view: a tree pops the smaller ones, which are hidden behind it from then on, and
+    /// what remains on top is the first tree it cannot see past. Every tree is pushed and popped
+    /// once per walk, so this is linear in the number of trees.
+    fn survey(&self) -> Survey {
+        let (height, width) = (self.height(), self.width());
+        let mut survey = Survey {
+            visible: vec![vec![false; width]; height],
+            scores: vec![vec![1; width]; height],
+        };
+
+        for line_idx in 0..height {
+            let line: Vec<(usize, usize)> = (0..width).map(|col| (line_idx, col)).collect();
+            self.look_back(line.iter(), &mut survey);
+            self.look_back(line.iter().rev(), &mut survey);
+        }
+        for col_idx in 0..width {
+            let column: Vec<(usize, usize)> = (0..height).map(|line| (line, col_idx)).collect();
+            self.look_back(column.iter(), &mut survey);
+            self.look_back(column.iter().rev(), &mut survey);
+        }
+        survey
+    }
+
+    /// Walks `cells` in order, each tree looking back towards the start of the walk.
+    fn look_back<'a, I>(&self, cells: I, survey: &mut Survey)
+    where
+        I: Iterator<Item = &'a (usize, usize)>,
+    {
+        // positions in the walk, for trees of decreasing (or equal) heights
+        let mut stack: Vec<(usize, u32)> = Vec::new();
+        for (pos, (line_idx, col_idx)) in cells.enumerate() {
+            let tree = self[*line_idx][*col_idx];
+            while stack.last().is_some_and(|(_, h)| *h < tree) {
+                stack.pop();
+            }
+            let distance = match stack.last() {
+                Some((blocking, _)) => pos - blocking,
+                None => {
+                    survey.visible[*line_idx][*col_idx] = true;
+                    pos
+                }
+            };
+            survey.scores[*line_idx][*col_idx] *= distance as u64;
+            stack.push((pos, tree));
+        }
+    }
 }
 
 struct GridIter<'a> {
@@ -44,241 +129,162 @@ impl<'a> Iterator for GridIter<'a> {
     type Item = (u32, usize, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.col_idx < self.data[0].len() - 1 {
-            self.col_idx += 1;
-        } else {
-            if self.line_idx < self.data.grid.len() - 1 {
-                self.col_idx = 0;
-                self.line_idx += 1;
-            } else {
-                return None;
-            }
+        if self.line_idx >= self.data.height() || self.data.width() == 0 {
+            return None;
         }
 
-        return Some((
+        let item = (
             self.data[self.line_idx][self.col_idx],
             self.line_idx,
             self.col_idx,
-        ));
-    }
-}
-
-fn part1(contents: &String) {
-    let grid = make_tree_grid(contents);
-    let mut visible_trees: HashSet<(usize, usize)> = HashSet::new();
-
-    for line_idx in 0..grid.len() {
-        let mut line = get_line(&grid, line_idx);
-
-        // From left
-        visible_trees.insert((line_idx, 0));
-        let mut cur_height = line[0];
-        for (col_idx, tree) in line.iter().enumerate() {
-            if col_idx == 0 {
-                continue;
-            }
-            if *tree > cur_height {
-                cur_height = *tree;
-                visible_trees.insert((line_idx, col_idx));
-            }
-        }
-
-        // From right
-        line.reverse();
-        visible_trees.insert((line_idx, line.len() - 1));
-        let mut cur_height = line[0];
-        for (rev_col_idx, tree) in line.iter().enumerate() {
-            if rev_col_idx == 0 {
-                continue;
-            }
-            if *tree > cur_height {
-                cur_height = *tree;
-                visible_trees.insert((line_idx, line.len() - 1 - rev_col_idx));
-            }
+        );
+        self.col_idx += 1;
+        if self.col_idx == self.data.width() {
+            self.col_idx = 0;
+            self.line_idx += 1;
         }
+        Some(item)
     }
-    for col_idx in 0..grid[0].len() {
-        let mut col = get_column(&grid, col_idx);
-
-        // From top
-        visible_trees.insert((0, col_idx));
-        let mut cur_height = col[0];
-        for (line_idx, tree) in col.iter().enumerate() {
-            if line_idx == 0 {
-                continue;
-            }
-            if *tree > cur_height {
-                cur_height = *tree;
-                visible_trees.insert((line_idx, col_idx));
-            }
-        }
-
-        // From bottom
-        col.reverse();
-        visible_trees.insert((col.len() - 1, col_idx));
-        let mut cur_height = col[0];
-        for (rev_line_idx, tree) in col.iter().enumerate() {
-            if rev_line_idx == 0 {
-                continue;
-            }
-            if *tree > cur_height {
-                cur_height = *tree;
-                visible_trees.insert((col.len() - 1 - rev_line_idx, col_idx));
-            }
-        }
-    }
-
-    println!("Trees visible: {}", visible_trees.len());
 }
 
-fn part2_with_iter(contents: &String) {
-    let grid = Grid {
-        grid: make_tree_grid(contents),
-    };
-    let mut best_score = 0;
-
-    for (_tree, line_idx, col_idx) in grid.iter() {
-        let score = score_for(&grid.grid, line_idx, col_idx);
-
-        if score > best_score {
-            best_score = score;
-        }
-    }
-
-    println!("Best score {}", best_score);
+/// For each tree of a grid, whether it can be seen from outside and its scenic score.
+struct Survey {
+    visible: Vec<Vec<bool>>,
+    scores: Vec<Vec<u64>>,
 }
 
-fn part2(contents: &String) {
-    let grid = make_tree_grid(contents);
-    let mut best_score = 0;
-
-    for line_idx in 0..grid.len() - 1 {
-        for col_idx in 0..grid[0].len() - 1 {
-            let score = score_for(&grid, line_idx, col_idx);
-
-            if score > best_score {
-                best_score = score;
-            }
-        }
-    }
-
-    println!("Best score {}", best_score);
-}
-
-fn score_for(grid: &Vec<Vec<u32>>, line_idx: usize, col_idx: usize) -> u32 {
-    let mut ls = 0;
-    let mut rs = 0;
-    let mut us = 0;
-    let mut ds = 0;
-    let view_height = grid[line_idx][col_idx];
-
-    // To left
-    if col_idx > 0 {
-        for ci in 1..=col_idx {
-            let tree_height = grid[line_idx][col_idx - ci];
-            ls += 1;
-            if tree_height >= view_height {
-                break;
-            }
-        }
-    }
-    // To right
-    if col_idx < grid[0].len() - 1 {
-        for ci in col_idx + 1..grid[0].len() {
-            let tree_height = grid[line_idx][ci];
-            rs += 1;
-            if tree_height >= view_height {
-                break;
-            }
-        }
-    }
-    // To up
-    if line_idx > 0 {
-        for li in 1..=line_idx {
-            let tree_height = grid[line_idx - li][col_idx];
-            us += 1;
-            if tree_height >= view_height {
-                break;
-            }
-        }
-    }
-    // To down
-    if line_idx < grid.len() - 1 {
-        for li in line_idx + 1..grid.len() {
-            let tree_height = grid[li][col_idx];
-            ds += 1;
-            if tree_height >= view_height {
-                break;
-            }
-        }
-    }
-
-    let score = ls * rs * us * ds;
-    // println!(
-    //     "Score for {}, {} is {} * {} * {} * {} = {}",
-    //     line_idx, col_idx, us, ls, rs, ds, score
-    // );
-
-    score
-}
-
-fn make_tree_grid(contents: &String) -> Vec<Vec<u32>> {
-    let mut grid = Vec::new();
-    for line in contents.lines() {
-        let mut line_vec = Vec::new();
-        for tree in line.chars() {
-            line_vec.push(tree.to_digit(10).unwrap());
-        }
-
-        grid.push(line_vec);
-    }
-
-    grid
-}
-
-fn get_line(grid: &Vec<Vec<u32>>, line_idx: usize) -> Vec<u32> {
-    grid[line_idx].clone()
+fn part1(contents: &String) -> Result<usize, String> {
+    let grid = Grid::parse(contents)?;
+    let survey = grid.survey();
+    Ok(grid
+        .iter()
+        .filter(|(_, line_idx, col_idx)| survey.visible[*line_idx][*col_idx])
+        .count())
 }
 
-fn get_column(grid: &Vec<Vec<u32>>, column_idx: usize) -> Vec<u32> {
-    let mut col: Vec<u32> = Vec::new();
-    for line in grid.iter() {
-        col.push(line[column_idx]);
-    }
-    col
+fn part2(contents: &String) -> Result<u64, String> {
+    let grid = Grid::parse(contents)?;
+    let survey = grid.survey();
+    Ok(grid
+        .iter()
+        .map(|(_, line_idx, col_idx)| survey.scores[line_idx][col_idx])
+        .max()
+        .unwrap_or(0))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+
+    /// Scans outward from the tree in the four directions.
+    fn score_for(grid: &Grid, line_idx: usize, col_idx: usize) -> u64 {
+        let view_height = grid[line_idx][col_idx];
+        let distance = |trees: &mut dyn Iterator<Item = u32>| {
+            let mut seen = 0;
+            for tree_height in trees {
+                seen += 1;
+                if tree_height >= view_height {
+                    break;
+                }
+            }
+            seen
+        };
+
+        let line = &grid[line_idx];
+        let column: Vec<u32> = grid.grid.iter().map(|line| line[col_idx]).collect();
+        distance(&mut line[..col_idx].iter().rev().copied())
+            * distance(&mut line[col_idx + 1..].iter().copied())
+            * distance(&mut column[..line_idx].iter().rev().copied())
+            * distance(&mut column[line_idx + 1..].iter().copied())
+    }
 
-    #[test]
-    fn test_parts() {
-        let test_contents = String::from_str(
+    fn example() -> String {
+        String::from_str(
             "30373
 25512
 65332
 33549
 35390",
         )
-        .unwrap();
+        .unwrap()
+    }
 
-        part1(&test_contents);
-        part2(&test_contents);
+    #[test]
+    fn test_parts() {
+        let test_contents = example();
+
+        assert_eq!(part1(&test_contents), Ok(21));
+        assert_eq!(part2(&test_contents), Ok(8));
     }
 
     #[test]
     fn test_grid() {
-        let test_contents = String::from_str(
-            "30373
-25512
-65332
-33549
-35390",
-        )
-        .unwrap();
+        let grid = Grid::parse(&example()).unwrap();
+        let cells: Vec<(u32, usize, usize)> = grid.iter().collect();
+        assert_eq!(cells.len(), 25);
+        assert_eq!(cells[0], (3, 0, 0));
+        assert_eq!(cells[5], (2, 1, 0));
+        assert_eq!(cells[24], (0, 4, 4));
+
+        assert_eq!(Grid::parse("").unwrap().iter().count(), 0);
+        assert_eq!(
+            Grid::parse("123\n45").err(),
+            Some("Line 2: expected 3 trees, found 2".to_string())
+        );
+        assert_eq!(
+            Grid::parse("12a").err(),
+            Some("Line 1: 'a' is not a tree height".to_string())
+        );
+    }
+
+    #[test]
+    fn test_survey() {
+        let grid = Grid::parse(&example()).unwrap();
+        let survey = grid.survey();
+        for (_, line_idx, col_idx) in grid.iter() {
+            assert_eq!(
+                survey.scores[line_idx][col_idx],
+                score_for(&grid, line_idx, col_idx),
+                "score of ({}, {})",
+                line_idx,
+                col_idx
+            );
+        }
+        assert_eq!(survey.scores[1][2], 4);
+        assert_eq!(survey.scores[3][2], 8);
+        // the middle 5 of the second row is visible from the left and top
+        assert!(survey.visible[1][1]);
+        // the 1 in the middle of the second row is hidden
+        assert!(!survey.visible[1][3]);
+    }
+
+    #[test]
+    fn test_edges() {
+        let grid = Grid::parse(&example()).unwrap();
+        let survey = grid.survey();
+        let (height, width) = (grid.height(), grid.width());
+        for (_, line_idx, col_idx) in grid.iter() {
+            let on_edge =
+                line_idx == 0 || col_idx == 0 || line_idx == height - 1 || col_idx == width - 1;
+            if on_edge {
+                assert!(survey.visible[line_idx][col_idx]);
+                assert_eq!(survey.scores[line_idx][col_idx], 0);
+            }
+        }
 
-        let grid = make_tree_grid(&test_contents);
-        score_for(&grid, 2, 0);
+        // the best tree can be next to the last row and column
+        assert_eq!(part2(&"11111\n11111\n11191\n11111".to_string()), Ok(3 * 2));
+        assert_eq!(
+            part2(&"1111\n1111\n1111\n1191\n1111".to_string()),
+            Ok(2 * 3)
+        );
+
+        // a single tree, row or column is seen from everywhere and sees nothing
+        assert_eq!(part1(&"7".to_string()), Ok(1));
+        assert_eq!(part2(&"7".to_string()), Ok(0));
+        assert_eq!(part1(&"5555".to_string()), Ok(4));
+        assert_eq!(part1(&"1\n2\n1".to_string()), Ok(3));
+        assert_eq!(part2(&"1\n2\n1".to_string()), Ok(0));
     }
 }
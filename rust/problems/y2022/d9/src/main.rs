@@ -1,8 +1,13 @@
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::str::FromStr;
 use std::vec::Vec;
+use utils::debug;
+use utils::viz::{Frame, Playback, Recorder};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
     UP,
     RIGHT,
@@ -22,6 +27,92 @@ impl Direction {
     }
 }
 
+/// Moves the head `steps` times in `direction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Command {
+    direction: Direction,
+    steps: u32,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::UP => "U",
+            Direction::RIGHT => "R",
+            Direction::DOWN => "D",
+            Direction::LEFT => "L",
+        };
+        write!(f, "{} {}", direction, self.steps)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommandError {
+    /// Not a direction and a number of steps separated by a space
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    Direction {
+        line: usize,
+        found: String,
+    },
+    Steps {
+        line: usize,
+        found: String,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Syntax { line, text } => {
+                write!(
+                    f,
+                    "Line {}: expected a command like `R 4`, found {:?}",
+                    line, text
+                )
+            }
+            CommandError::Direction { line, found } => {
+                write!(f, "Line {}: unknown direction {:?}", line, found)
+            }
+            CommandError::Steps { line, found } => {
+                write!(f, "Line {}: bad number of steps {:?}", line, found)
+            }
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+/// Reads the motions of the head, one per line. Lines are numbered from 1 in the errors.
+fn parse_commands(contents: &str) -> Result<Vec<Command>, CommandError> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(idx, text)| {
+            let line = idx + 1;
+            let (direction, steps) = text.split_once(' ').ok_or_else(|| CommandError::Syntax {
+                line,
+                text: text.to_string(),
+            })?;
+            Ok(Command {
+                direction: Direction::from_string(direction).map_err(|_| {
+                    CommandError::Direction {
+                        line,
+                        found: direction.to_string(),
+                    }
+                })?,
+                steps: steps.parse::<u32>().map_err(|_| CommandError::Steps {
+                    line,
+                    found: steps.to_string(),
+                })?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug)]
 struct Knot {
     x: i32,
     y: i32,
@@ -65,70 +156,225 @@ impl Knot {
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
-
-    part1(&contents);
-    part2(&contents);
+/// Knots tied one after the other, all starting at the origin. The first one is the head, and
+/// the positions of the tracked knot (the tail by default) are remembered.
+struct Rope {
+    knots: Vec<Knot>,
+    tracked: usize,
+    visited: HashSet<(i32, i32)>,
 }
 
-fn part1(contents: &String) {
-    let mut head = Knot { x: 0, y: 0 };
-    let mut tail = Knot { x: 0, y: 0 };
-    let mut visited: HashSet<(i32, i32)> = HashSet::new();
+impl Rope {
+    fn new(length: usize) -> Self {
+        assert!(length > 0, "A rope has at least a head");
+        Rope {
+            knots: vec![Knot { x: 0, y: 0 }; length],
+            tracked: length - 1,
+            visited: HashSet::from([(0, 0)]),
+        }
+    }
 
-    for line in contents.lines() {
-        let tokens: Vec<&str> = line.split(" ").collect();
-        let direction = Direction::from_string(tokens[0]).unwrap();
-        let step = tokens[1].parse::<u32>().unwrap();
+    /// Remembers the positions of the knot at `idx` instead of the tail's.
+    fn tracking(mut self, idx: usize) -> Self {
+        assert!(idx < self.knots.len(), "No knot {} in the rope", idx);
+        self.tracked = idx;
+        let knot = &self.knots[idx];
+        self.visited = HashSet::from([(knot.x, knot.y)]);
+        self
+    }
 
-        for i in 0..step {
-            head.move_in_direction(&direction);
-            tail.follow(&head);
+    fn step(&mut self, direction: &Direction) {
+        self.knots[0].move_in_direction(direction);
+        for j in 0..self.knots.len() - 1 {
+            let leader = self.knots[j].clone();
+            self.knots[j + 1].follow(&leader);
+        }
+        let knot = &self.knots[self.tracked];
+        self.visited.insert((knot.x, knot.y));
+    }
 
-            visited.insert((tail.x, tail.y));
+    fn apply(&mut self, command: &Command) {
+        for _ in 0..command.steps {
+            self.step(&command.direction);
         }
+    }
 
-        println!(
-            "Head at {},{}, Tail at {}, {}",
-            head.x, head.y, tail.x, tail.y
-        );
+    /// The positions the tracked knot has been at.
+    fn visited(&self) -> &HashSet<(i32, i32)> {
+        &self.visited
+    }
+
+    /// Draws the rope in `area`, given as `(min_x, min_y, max_x, max_y)`, like the puzzle does.
+    fn render(&self, area: (i32, i32, i32, i32)) -> String {
+        draw(area, &knots_cells(&self.knots))
     }
 
-    println!("Visited {} positions", visited.len());
+    /// Draws the positions visited by the tracked knot in `area`, like the puzzle does.
+    fn render_visited(&self, area: (i32, i32, i32, i32)) -> String {
+        let mut cells: HashMap<(i64, i64), char> = self
+            .visited
+            .iter()
+            .map(|(x, y)| ((*x as i64, -*y as i64), '#'))
+            .collect();
+        cells.insert((0, 0), 's');
+        draw(area, &cells)
+    }
 }
 
-fn part2(contents: &String) {
-    let mut knots: Vec<Knot> = Vec::new();
-    for _i in 0..10 {
-        knots.push(Knot { x: 0, y: 0 });
+/// Draws screen cells in an area given in puzzle coordinates, `y` going up.
+fn draw(
+    (min_x, min_y, max_x, max_y): (i32, i32, i32, i32),
+    cells: &HashMap<(i64, i64), char>,
+) -> String {
+    (min_y..=max_y)
+        .rev()
+        .map(|y| {
+            (min_x..=max_x)
+                .map(|x| *cells.get(&(x as i64, -y as i64)).unwrap_or(&'.'))
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn main() {
+    let contents = fs::read_to_string("input.txt").expect("Should have been able to read the file");
+    let playback = Playback::from_args(env::args()).expect("Bad arguments");
+
+    let visited = part1(&contents, playback.as_ref()).expect("Should have been able to simulate");
+    println!("Visited {} positions", visited);
+    let visited = part2(&contents, playback.as_ref()).expect("Should have been able to simulate");
+    println!("Visited {} positions", visited);
+
+    // e.g. --area -11,-5,14,15 --knots 10 --knot 9 to diff against the puzzle text
+    let arg = |name: &str| env::args().skip_while(|arg| arg != name).nth(1);
+    if let Some(area) = arg("--area") {
+        let area: Vec<i32> = area
+            .split(',')
+            .map(|v| v.parse::<i32>().expect("Bad --area value"))
+            .collect();
+        let area = match area[..] {
+            [min_x, min_y, max_x, max_y] => (min_x, min_y, max_x, max_y),
+            _ => panic!("--area takes min_x,min_y,max_x,max_y"),
+        };
+        let length = arg("--knots").map_or(10, |v| v.parse().expect("Bad --knots value"));
+        let knot = arg("--knot").map_or(length - 1, |v| v.parse().expect("Bad --knot value"));
+        print_states(&contents, length, knot, area).expect("Should have been able to simulate");
     }
-    let mut visited: HashSet<(i32, i32)> = HashSet::new();
+}
 
-    for line in contents.lines() {
-        let tokens: Vec<&str> = line.split(" ").collect();
-        let direction = Direction::from_string(tokens[0]).unwrap();
-        let step = tokens[1].parse::<u32>().unwrap();
+/// Prints the rope after each command and the positions visited by `knot`, like the puzzle.
+fn print_states(
+    contents: &str,
+    length: usize,
+    knot: usize,
+    area: (i32, i32, i32, i32),
+) -> Result<(), CommandError> {
+    let mut rope = Rope::new(length).tracking(knot);
+    for command in parse_commands(contents)? {
+        rope.apply(&command);
+        println!("== {} ==\n", command);
+        println!("{}\n", rope.render(area));
+    }
+    println!("{}", rope.render_visited(area));
+    println!("Knot {} visited {} positions", knot, rope.visited().len());
+    Ok(())
+}
 
-        for _i in 0..step {
-            knots[0].move_in_direction(&direction);
+/// Draws the knots like the puzzle does: the head is `H`, the other knots are numbered (or `T`
+/// when there is only a tail), and a knot hides the ones that follow it.
+fn knots_cells(knots: &[Knot]) -> HashMap<(i64, i64), char> {
+    let mut cells = HashMap::new();
+    // y goes up in the puzzle, and down on screen
+    cells.insert((0, 0), 's');
+    for (i, knot) in knots.iter().enumerate().rev() {
+        let c = match i {
+            0 => 'H',
+            _ if knots.len() == 2 => 'T',
+            _ => char::from_digit(i as u32, 36).unwrap_or('?'),
+        };
+        cells.insert((knot.x as i64, -knot.y as i64), c);
+    }
+    cells
+}
 
-            for j in 0..knots.len() - 1 {
-                let leader = knots[j].clone();
-                let follower = &mut knots[j + 1];
-                follower.follow(&leader);
-            }
+fn replay(recorder: Option<Recorder>, playback: Option<&Playback>) {
+    if let (Some(recorder), Some(playback)) = (recorder, playback) {
+        recorder
+            .replay(playback)
+            .expect("Could not replay the simulation");
+    }
+}
 
-            visited.insert((knots[9].x, knots[9].y));
+/// Moves a rope of `length` knots, recording it after each command, and counts the positions
+/// visited by its tail.
+fn simulate(
+    contents: &str,
+    length: usize,
+    playback: Option<&Playback>,
+) -> Result<usize, CommandError> {
+    let commands = parse_commands(contents)?;
+    let mut recorder = playback.map(|p| p.recorder());
+    let mut rope = Rope::new(length);
+
+    for command in commands.iter() {
+        rope.apply(command);
+        debug!("After {:?}: {:?}", command, rope.knots);
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(|| {
+                let label = format!("== {} ==", command);
+                (label, Frame::Cells(knots_cells(&rope.knots)))
+            });
         }
     }
 
-    println!("Visited {} positions", visited.len());
+    replay(recorder, playback);
+    Ok(rope.visited().len())
+}
+
+fn part1(contents: &String, playback: Option<&Playback>) -> Result<usize, CommandError> {
+    simulate(contents, 2, playback)
+}
+
+fn part2(contents: &String, playback: Option<&Playback>) -> Result<usize, CommandError> {
+    simulate(contents, 10, playback)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_knots_cells() {
+        // after `U 4` in the first example of part 2
+        let knots: Vec<Knot> = [
+            (4, 4),
+            (4, 3),
+            (4, 2),
+            (3, 2),
+            (2, 2),
+            (1, 1),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+        ]
+        .iter()
+        .map(|(x, y)| Knot { x: *x, y: *y })
+        .collect();
+
+        let mut recorder = Recorder::new();
+        recorder.push("== U 4 ==".to_string(), Frame::Cells(knots_cells(&knots)));
+        assert_eq!(
+            recorder.render(0).unwrap(),
+            "....H
+....1
+..432
+.5...
+6...."
+        );
+    }
 
     #[test]
     fn test_parts() {
@@ -144,8 +390,8 @@ R 2",
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part1(&test_contents, None), Ok(13));
+        assert_eq!(part2(&test_contents, None), Ok(1));
 
         let test_contents = String::from_str(
             "R 5
@@ -159,7 +405,86 @@ U 20",
         )
         .unwrap();
 
-        part1(&test_contents);
-        part2(&test_contents);
+        assert_eq!(part2(&test_contents, None), Ok(36));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_commands("R 4\nU4"),
+            Err(CommandError::Syntax {
+                line: 2,
+                text: "U4".to_string()
+            })
+        );
+        assert_eq!(
+            parse_commands("X 4"),
+            Err(CommandError::Direction {
+                line: 1,
+                found: "X".to_string()
+            })
+        );
+        assert_eq!(
+            parse_commands("R 4\nL 1\nD -2"),
+            Err(CommandError::Steps {
+                line: 3,
+                found: "-2".to_string()
+            })
+        );
+        assert!(part1(&"R 4\nU 4 2".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_rope() {
+        let commands = parse_commands("R 4\nU 4\nL 3\nD 1").unwrap();
+        assert_eq!(commands[2].to_string(), "L 3");
+        let area = (0, 0, 5, 4);
+        let mut rope = Rope::new(2);
+        let mut states = Vec::new();
+        for command in commands.iter() {
+            rope.apply(command);
+            states.push(rope.render(area));
+        }
+        // the end of each motion in the first example of the puzzle
+        assert_eq!(
+            states,
+            [
+                "......\n......\n......\n......\ns..TH.",
+                "....H.\n....T.\n......\n......\ns.....",
+                ".HT...\n......\n......\n......\ns.....",
+                "..T...\n.H....\n......\n......\ns.....",
+            ]
+        );
+
+        let mut rope = Rope::new(10);
+        rope.apply(&commands[0]);
+        rope.apply(&commands[1]);
+        assert_eq!(rope.render(area), "....H.\n....1.\n..432.\n.5....\n6.....");
+    }
+
+    #[test]
+    fn test_visited() {
+        let commands = parse_commands("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2").unwrap();
+        let mut rope = Rope::new(2);
+        for command in commands.iter() {
+            rope.apply(command);
+        }
+        assert_eq!(
+            rope.render_visited((0, 0, 5, 4)),
+            "..##..\n...##.\n.####.\n....#.\ns###.."
+        );
+
+        // the head of a longer rope goes everywhere the head of a short one does
+        let mut head = Rope::new(10).tracking(0);
+        let mut middle = Rope::new(10).tracking(1);
+        let mut short = Rope::new(2);
+        for command in commands.iter() {
+            head.apply(command);
+            middle.apply(command);
+            short.apply(command);
+        }
+        assert_eq!(middle.visited(), short.visited());
+        assert!(head.visited().is_superset(short.visited()));
+        assert_eq!(Rope::new(1).visited().len(), 1);
     }
 }
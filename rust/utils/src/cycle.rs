@@ -0,0 +1,262 @@
+use crate::debug;
+use num::PrimInt;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// A cycle in a sequence of states: the state at step `start + length` is the same as the one at
+/// step `start`, and the sequence repeats forever from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle starting at step {} of length {}",
+            self.start, self.length
+        )
+    }
+}
+
+/// Floyd's tortoise and hare. `f` must be a pure function of the state.
+pub fn floyd<T, F>(initial: T, f: F) -> Cycle
+where
+    T: Eq + Clone,
+    F: Fn(&T) -> T,
+{
+    let mut tortoise = f(&initial);
+    let mut hare = f(&tortoise);
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&f(&hare));
+    }
+
+    // find the start of the cycle
+    let mut start = 0;
+    tortoise = initial;
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&hare);
+        start += 1;
+    }
+
+    // find its length
+    let mut length = 1;
+    hare = f(&tortoise);
+    while tortoise != hare {
+        hare = f(&hare);
+        length += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Brent's algorithm. Same contract as `floyd`, but with fewer calls to `f`.
+pub fn brent<T, F>(initial: T, f: F) -> Cycle
+where
+    T: Eq + Clone,
+    F: Fn(&T) -> T,
+{
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = f(&initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = f(&hare);
+        length += 1;
+    }
+
+    // put the hare `length` steps ahead of the tortoise, then move both until they meet
+    tortoise = initial.clone();
+    hare = initial;
+    for _ in 0..length {
+        hare = f(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Remembers at which step each state fingerprint was first seen.
+///
+/// Useful when the full state is too big to compare (or to keep around) and the simulation is
+/// not a pure function, e.g. when only the top rows of a rock tower matter.
+#[derive(Debug)]
+pub struct CycleDetector<K: Hash + Eq> {
+    first_seen: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> Default for CycleDetector<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq> CycleDetector<K> {
+    pub fn new() -> Self {
+        CycleDetector {
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Records `fingerprint` for `step`, and returns the cycle if it had been seen before.
+    pub fn observe(&mut self, fingerprint: K, step: usize) -> Option<Cycle> {
+        match self.first_seen.get(&fingerprint) {
+            Some(start) => Some(Cycle {
+                start: *start,
+                length: step - start,
+            }),
+            None => {
+                self.first_seen.insert(fingerprint, step);
+                None
+            }
+        }
+    }
+}
+
+/// The value of a metric after `target` steps, and the cycle used to get there (if any).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extrapolation<M> {
+    pub value: M,
+    pub cycle: Option<Cycle>,
+}
+
+/// Runs a simulation until its fingerprint repeats, and extrapolates `metric` to `target` steps.
+///
+/// `metric` must grow by the same amount at each turn of the cycle (e.g. the height of a tower,
+/// a number of inspected items). When `target` is reached before any cycle is found, the value
+/// is the simulated one.
+pub fn extrapolate<S, K, M, F, G, H>(
+    mut state: S,
+    target: usize,
+    mut step: F,
+    fingerprint: G,
+    metric: H,
+) -> Extrapolation<M>
+where
+    K: Hash + Eq,
+    M: PrimInt,
+    F: FnMut(&mut S),
+    G: Fn(&S) -> K,
+    H: Fn(&S) -> M,
+{
+    let mut detector = CycleDetector::new();
+    let mut metrics = Vec::new();
+
+    for current in 0..=target {
+        metrics.push(metric(&state));
+        if let Some(cycle) = detector.observe(fingerprint(&state), current) {
+            debug!("Found {}", cycle);
+            let gain_per_cycle = metrics[cycle.start + cycle.length] - metrics[cycle.start];
+            let nb_cycles = (target - cycle.start) / cycle.length;
+            let remainder = (target - cycle.start) % cycle.length;
+            let nb_cycles = M::from(nb_cycles).expect("number of cycles should fit in the metric");
+            return Extrapolation {
+                value: metrics[cycle.start + remainder] + nb_cycles * gain_per_cycle,
+                cycle: Some(cycle),
+            };
+        }
+        if current < target {
+            step(&mut state);
+        }
+    }
+
+    Extrapolation {
+        value: metrics[target],
+        cycle: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2
+    fn next(x: &u32) -> u32 {
+        if *x == 5 {
+            2
+        } else {
+            x + 1
+        }
+    }
+
+    #[test]
+    fn test_floyd_and_brent() {
+        let expected = Cycle {
+            start: 2,
+            length: 4,
+        };
+        assert_eq!(floyd(0, next), expected);
+        assert_eq!(brent(0, next), expected);
+        assert_eq!(
+            floyd(3, next),
+            Cycle {
+                start: 0,
+                length: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector = CycleDetector::new();
+        assert_eq!(detector.observe("a", 0), None);
+        assert_eq!(detector.observe("b", 1), None);
+        assert_eq!(
+            detector.observe("a", 2),
+            Some(Cycle {
+                start: 0,
+                length: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        // (position in the cycle, total distance travelled), where each step moves by the
+        // position's value
+        let res = extrapolate(
+            (0u32, 0u64),
+            1_000_000_000_000,
+            |s| {
+                s.1 += s.0 as u64;
+                s.0 = next(&s.0);
+            },
+            |s| s.0,
+            |s| s.1,
+        );
+        assert_eq!(
+            res.cycle,
+            Some(Cycle {
+                start: 2,
+                length: 4
+            })
+        );
+        // 1 for the first 2 steps, then 14 per cycle of 4 steps
+        assert_eq!(res.value, 1 + 14 * 249_999_999_999 + 2 + 3);
+
+        let short = extrapolate(
+            (0u32, 0u64),
+            3,
+            |s| s.0 = next(&s.0),
+            |s| s.0,
+            |s| s.0 as u64,
+        );
+        assert_eq!(short.value, 3);
+        assert_eq!(short.cycle, None);
+    }
+}
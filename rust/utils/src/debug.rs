@@ -1,7 +1,286 @@
+//! Leveled debug logging, configured through the `DEBUG` environment variable.
+//!
+//! `DEBUG` holds a comma separated list of directives. A bare level sets the default level, and
+//! `name=level` sets the level for the modules matching `name`, e.g. `DEBUG=d16=trace,graph=info`.
+//! `DEBUG=true` (or any other value that is not a level) turns on the `debug` level everywhere,
+//! and nothing is logged when `DEBUG` is not set.
+//!
+//! Logs go to stdout, or are appended to the file named by `DEBUG_FILE` when it is set.
+//!
+//! The macros (`error!`, `warn!`, `info!`, `debug!`, `trace!`) only format their arguments when
+//! the level is enabled for the calling module.
+
 use std::env;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, Once, RwLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        // pad so that messages line up
+        f.pad(name)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("Unknown log level {}", s)),
+        }
+    }
+}
+
+/// Which levels are enabled, globally and per module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    default: Option<Level>,
+    directives: Vec<(String, Option<Level>)>,
+}
+
+impl Filter {
+    /// Parses a filter with the syntax of the `DEBUG` variable. `off` disables a module.
+    pub fn parse(spec: &str) -> Self {
+        let mut filter = Filter::default();
+
+        for directive in spec.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            match directive.split_once('=') {
+                Some((name, level)) => {
+                    filter
+                        .directives
+                        .push((name.trim().to_string(), parse_level(level.trim())));
+                }
+                None => filter.default = parse_level(directive),
+            }
+        }
+        if spec.trim().is_empty() {
+            filter.default = Some(Level::Debug);
+        }
+
+        filter
+    }
+
+    fn off() -> Self {
+        Filter::default()
+    }
+
+    /// The most verbose level enabled for the module at `target` (a `module_path!()`).
+    pub fn level_for(&self, target: &str) -> Option<Level> {
+        let mut best: Option<&(String, Option<Level>)> = None;
+        for directive in self.directives.iter() {
+            if !matches_target(&directive.0, target) {
+                continue;
+            }
+            if best.is_none() || best.unwrap().0.len() < directive.0.len() {
+                best = Some(directive);
+            }
+        }
+
+        match best {
+            Some((_, level)) => *level,
+            None => self.default,
+        }
+    }
+
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        match self.level_for(target) {
+            Some(max) => level <= max,
+            None => false,
+        }
+    }
+
+    fn max_level(&self) -> u8 {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .chain([self.default])
+            .flatten()
+            .map(|level| level as u8)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn parse_level(level: &str) -> Option<Level> {
+    if level.eq_ignore_ascii_case("off") {
+        return None;
+    }
+    Some(Level::from_str(level).unwrap_or(Level::Debug))
+}
+
+/// `name` matches a module path if it is one of its prefixes or suffixes, e.g. `graph`, `utils`
+/// and `utils::graph` all match `utils::graph`.
+fn matches_target(name: &str, target: &str) -> bool {
+    target == name
+        || target.starts_with(&format!("{}::", name))
+        || target.ends_with(&format!("::{}", name))
+}
+
+static INIT: Once = Once::new();
+// most verbose level enabled for any module, to bail out early without taking any lock
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(0);
+static FILTER: RwLock<Option<Filter>> = RwLock::new(None);
+static OUTPUT: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+fn init_from_env() {
+    INIT.call_once(|| {
+        let filter = match env::var("DEBUG") {
+            Ok(spec) => Filter::parse(&spec),
+            Err(_) => Filter::off(),
+        };
+        set_filter(filter);
+
+        if let Ok(path) = env::var("DEBUG_FILE") {
+            if let Err(e) = log_to_file(&path) {
+                eprintln!("Cannot log to {}: {}", path, e);
+            }
+        }
+    });
+}
+
+fn set_filter(filter: Filter) {
+    MAX_LEVEL.store(filter.max_level(), Ordering::Relaxed);
+    *FILTER.write().unwrap() = Some(filter);
+}
+
+/// Replaces the filter read from the environment, e.g. to turn logs on from a test.
+pub fn configure(spec: &str) {
+    // make sure that a later first log does not override this with the environment
+    init_from_env();
+    set_filter(Filter::parse(spec));
+}
+
+/// Appends the logs to `path` instead of printing them.
+pub fn log_to_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *OUTPUT.lock().unwrap() = Some(LineWriter::new(file));
+    Ok(())
+}
+
+/// Goes back to printing the logs on stdout.
+pub fn log_to_stdout() {
+    *OUTPUT.lock().unwrap() = None;
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    init_from_env();
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    match FILTER.read().unwrap().as_ref() {
+        Some(filter) => filter.enabled(level, target),
+        None => false,
+    }
+}
+
+/// Writes a log line, without checking the filter. Use the macros instead.
+pub fn write(level: Level, target: &str, args: fmt::Arguments) {
+    let mut output = OUTPUT.lock().unwrap();
+    match output.as_mut() {
+        Some(file) => {
+            // losing a log line is not worth crashing the solver
+            let _ = writeln!(file, "[{:<5} {}] {}", level, target, args);
+        }
+        None => println!("[{:<5} {}] {}", level, target, args),
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        let level = $level;
+        if $crate::debug::enabled(level, module_path!()) {
+            $crate::debug::write(level, module_path!(), format_args!($($arg)+));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::debug::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::debug::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::debug::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::debug::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::debug::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_parse() {
+        let filter = Filter::parse("d16=trace,graph=info");
+        assert_eq!(filter.level_for("d16"), Some(Level::Trace));
+        assert_eq!(filter.level_for("utils::graph"), Some(Level::Info));
+        assert_eq!(filter.level_for("d15"), None);
+        assert!(filter.enabled(Level::Info, "utils::graph"));
+        assert!(!filter.enabled(Level::Debug, "utils::graph"));
+
+        let filter = Filter::parse("warn,utils=trace,utils::memo=off");
+        assert_eq!(filter.level_for("d1"), Some(Level::Warn));
+        assert_eq!(filter.level_for("utils::cycle"), Some(Level::Trace));
+        assert_eq!(filter.level_for("utils::memo"), None);
+        assert_eq!(filter.max_level(), Level::Trace as u8);
+
+        // backward compatible with DEBUG=true
+        assert_eq!(Filter::parse("true").level_for("d7"), Some(Level::Debug));
+        assert_eq!(Filter::parse("").level_for("d7"), Some(Level::Debug));
+    }
 
-pub fn debug(msg: String) {
-    if env::var("DEBUG").is_ok() {
-        println!("{}", msg);
+    #[test]
+    fn test_disabled_args_are_not_evaluated() {
+        configure("d1=trace");
+        let mut evaluated = false;
+        trace!("{}", {
+            evaluated = true;
+            "side effect"
+        });
+        assert!(!evaluated);
     }
 }
@@ -1,2 +1,9 @@
+pub mod cycle;
 pub mod debug;
 pub mod graph;
+pub mod memo;
+pub mod ocr;
+pub mod render;
+pub mod topk;
+pub mod viz;
+pub mod vm;
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Cache for the results of a (usually recursive) function, keyed by its argument.
+///
+/// The function is not stored in the cache: it is passed at each call, and receives the cache
+/// back so that it can recurse through it.
+///
+/// ```
+/// use utils::memo::Memo;
+///
+/// fn fib(memo: &mut Memo<u64, u64>, n: u64) -> u64 {
+///     memo.get_or_compute(n, |memo| match n {
+///         0 | 1 => n,
+///         _ => fib(memo, n - 1) + fib(memo, n - 2),
+///     })
+/// }
+///
+/// let mut memo = Memo::new();
+/// assert_eq!(fib(&mut memo, 90), 2880067194370816120);
+/// assert_eq!(memo.stats().misses, 91);
+/// ```
+#[derive(Debug)]
+pub struct Memo<K: Hash + Eq, V: Clone> {
+    cache: HashMap<K, V>,
+    hits: usize,
+    misses: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub size: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let calls = self.hits + self.misses;
+        let hit_rate = if calls == 0 {
+            0.0
+        } else {
+            100.0 * self.hits as f64 / calls as f64
+        };
+        write!(
+            f,
+            "{} calls, {} hits ({:.1}%), {} misses, {} cached values",
+            calls, self.hits, hit_rate, self.misses, self.size
+        )
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Default for Memo<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Memo<K, V> {
+    pub fn new() -> Self {
+        Memo {
+            cache: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the cached value for `key`, or computes it with `compute` and caches it.
+    pub fn get_or_compute<F>(&mut self, key: K, compute: F) -> V
+    where
+        F: FnOnce(&mut Self) -> V,
+    {
+        if let Some(value) = self.cache.get(&key) {
+            self.hits += 1;
+            return value.clone();
+        }
+
+        self.misses += 1;
+        let value = compute(self);
+        self.cache.insert(key, value.clone());
+        value
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.cache.get(key)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            size: self.cache.len(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+/// Key that compares and hashes an `Rc` by the address it points to, so that nodes of a tree or a
+/// graph can be used as keys without being `Hash` themselves.
+#[derive(Debug)]
+pub struct ByAddress<T>(pub Rc<T>);
+
+impl<T> Clone for ByAddress<T> {
+    fn clone(&self) -> Self {
+        ByAddress(self.0.clone())
+    }
+}
+
+impl<T> PartialEq for ByAddress<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Eq for ByAddress<T> {}
+
+impl<T> Hash for ByAddress<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_paths(memo: &mut Memo<(u32, u32), u64>, x: u32, y: u32) -> u64 {
+        memo.get_or_compute((x, y), |memo| {
+            if x == 0 || y == 0 {
+                return 1;
+            }
+            count_paths(memo, x - 1, y) + count_paths(memo, x, y - 1)
+        })
+    }
+
+    #[test]
+    fn test_recursive_memo() {
+        let mut memo = Memo::new();
+        assert_eq!(count_paths(&mut memo, 16, 16), 601080390);
+
+        let stats = memo.stats();
+        assert_eq!(stats.misses, 17 * 17 - 1);
+        assert_eq!(stats.size, 17 * 17 - 1);
+        // each of the 16 * 16 inner cells recurses twice, plus the initial call
+        assert_eq!(stats.hits, 2 * 16 * 16 + 1 - stats.misses);
+
+        memo.clear();
+        assert_eq!(memo.stats().size, 0);
+    }
+
+    #[test]
+    fn test_by_address() {
+        let a = Rc::new(1);
+        let b = Rc::new(1);
+        let mut memo: Memo<ByAddress<i32>, i32> = Memo::new();
+        memo.get_or_compute(ByAddress(a.clone()), |_| 10);
+        memo.get_or_compute(ByAddress(b.clone()), |_| 20);
+        assert_eq!(memo.get(&ByAddress(a)), Some(&10));
+        assert_eq!(memo.get(&ByAddress(b)), Some(&20));
+    }
+}
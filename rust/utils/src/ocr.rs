@@ -0,0 +1,286 @@
+//! Reads the block letters that some puzzles draw instead of giving a plain answer.
+//!
+//! Two fonts are known: the small one, 6 pixels high and (mostly) 4 wide, and the big one, 10
+//! pixels high and 6 wide. The font is chosen from the height of the drawing, and letters are
+//! split on the columns that have no lit pixel.
+
+use std::error::Error;
+use std::fmt;
+
+const SMALL_FONT: [(char, &str); 18] = [
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+const BIG_FONT: [(char, &str); 16] = [
+    (
+        'A',
+        "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'B',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####.",
+    ),
+    (
+        'C',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####.",
+    ),
+    (
+        'E',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'F',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'G',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#",
+    ),
+    (
+        'H',
+        "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'J',
+        "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###..",
+    ),
+    (
+        'K',
+        "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#",
+    ),
+    (
+        'L',
+        "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'N',
+        "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#",
+    ),
+    (
+        'O',
+        ".####.\n#....#\n#....#\n#....#\n#....#\n#....#\n#....#\n#....#\n#....#\n.####.",
+    ),
+    (
+        'P',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'R',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#",
+    ),
+    (
+        'X',
+        "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#",
+    ),
+    (
+        'Z',
+        "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######",
+    ),
+];
+
+/// A group of lit pixels that does not look like any known letter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    /// Index of the letter in the recognized text
+    pub index: usize,
+    /// First column of the glyph in the drawing
+    pub column: usize,
+    /// The glyph, drawn with `#` and `.`
+    pub drawing: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    /// Only drawings 6 or 10 pixels high can be read
+    UnsupportedHeight(usize),
+    /// Some glyphs could not be read. `partial` has a `?` for each of them.
+    UnknownGlyphs {
+        partial: String,
+        unknown: Vec<UnknownGlyph>,
+    },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::UnsupportedHeight(h) => {
+                write!(f, "No font for letters {} pixels high", h)
+            }
+            OcrError::UnknownGlyphs { partial, unknown } => {
+                write!(f, "Could only read {}", partial)?;
+                for glyph in unknown.iter() {
+                    write!(
+                        f,
+                        "\nUnknown letter {} at column {}:\n{}",
+                        glyph.index, glyph.column, glyph.drawing
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for OcrError {}
+
+/// Reads a drawing given as rows of pixels, `true` being lit.
+pub fn recognize(pixels: &[Vec<bool>]) -> Result<String, OcrError> {
+    let font: &[(char, &str)] = match pixels.len() {
+        6 => &SMALL_FONT,
+        10 => &BIG_FONT,
+        h => return Err(OcrError::UnsupportedHeight(h)),
+    };
+
+    let width = pixels.iter().map(|row| row.len()).max().unwrap_or(0);
+    let is_lit = |x: usize, y: usize| *pixels[y].get(x).unwrap_or(&false);
+    let is_blank_column = |x: usize| (0..pixels.len()).all(|y| !is_lit(x, y));
+
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    let mut x = 0;
+    while x < width {
+        if is_blank_column(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < width && !is_blank_column(x) {
+            x += 1;
+        }
+
+        let drawing = (0..pixels.len())
+            .map(|y| {
+                (start..x)
+                    .map(|gx| if is_lit(gx, y) { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        match font.iter().find(|(_, glyph)| trim(glyph) == drawing) {
+            Some((letter, _)) => text.push(*letter),
+            None => {
+                unknown.push(UnknownGlyph {
+                    index: text.chars().count(),
+                    column: start,
+                    drawing,
+                });
+                text.push('?');
+            }
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::UnknownGlyphs {
+            partial: text,
+            unknown,
+        })
+    }
+}
+
+/// Reads a drawing given as text, with `#` (or `█`) for lit pixels.
+pub fn recognize_text(drawing: &str) -> Result<String, OcrError> {
+    let pixels: Vec<Vec<bool>> = drawing
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#' || c == '█').collect())
+        .collect();
+    recognize(&pixels)
+}
+
+/// Removes the blank columns around a glyph of the font, as done when splitting a drawing.
+fn trim(glyph: &str) -> String {
+    let rows: Vec<&str> = glyph.lines().collect();
+    let is_blank_column = |x: usize| rows.iter().all(|row| row.as_bytes()[x] == b'.');
+    let width = rows[0].len();
+    let start = (0..width).find(|x| !is_blank_column(*x)).unwrap_or(0);
+    let end = (0..width).rfind(|x| !is_blank_column(*x)).unwrap_or(0);
+    rows.iter()
+        .map(|row| &row[start..=end])
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_font() {
+        let drawing = "\
+###..###..###...##..###...##...##..####.
+#..#.#..#.#..#.#..#.#..#.#..#.#..#.#....
+#..#.###..#..#.#..#.#..#.#..#.#....###..
+###..#..#.###..####.###..####.#.##.#....
+#.#..#..#.#....#..#.#.#..#..#.#..#.#....
+#..#.###..#....#..#.#..#.#..#..###.#....";
+        assert_eq!(recognize_text(drawing), Ok("RBPARAGF".to_string()));
+
+        // letters starting with blank columns, and the wider Y
+        let drawing = "\
+.###.#...#.####
+..#..#...#....#
+..#...#.#....#.
+..#....#....#..
+..#....#...#...
+.###...#...####";
+        assert_eq!(recognize_text(drawing), Ok("IYZ".to_string()));
+    }
+
+    #[test]
+    fn test_big_font() {
+        let drawing = BIG_FONT
+            .iter()
+            .map(|(_, glyph)| glyph.lines().collect::<Vec<&str>>())
+            .fold(vec![String::new(); 10], |mut rows, glyph| {
+                for (row, glyph_row) in rows.iter_mut().zip(glyph) {
+                    row.push_str(glyph_row);
+                    row.push_str("..");
+                }
+                rows
+            })
+            .join("\n");
+        assert_eq!(recognize_text(&drawing), Ok("ABCEFGHJKLNOPRXZ".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(recognize_text("#\n#"), Err(OcrError::UnsupportedHeight(2)));
+
+        let drawing = "\
+####..#.
+#.....#.
+###...#.
+#.....#.
+#.......
+#.....#.";
+        assert_eq!(
+            recognize_text(drawing),
+            Err(OcrError::UnknownGlyphs {
+                partial: "F?".to_string(),
+                unknown: vec![UnknownGlyph {
+                    index: 1,
+                    column: 6,
+                    drawing: "#\n#\n#\n#\n.\n#".to_string(),
+                }],
+            })
+        );
+    }
+}
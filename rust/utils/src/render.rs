@@ -0,0 +1,360 @@
+//! Exports grids and sparse cell sets as PNG images and animated GIFs.
+//!
+//! Each kind of cell is mapped to a color by a `Palette`, and drawn as a square of `scale` pixels.
+
+use crate::viz::{self, Frame, Playback, Recorder};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufWriter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub type Rgb = [u8; 3];
+
+/// Maps cell kinds to colors. Unknown kinds get the background color.
+#[derive(Debug, Clone)]
+pub struct Palette<K: Hash + Eq> {
+    colors: Vec<Rgb>,
+    indices: HashMap<K, u8>,
+}
+
+impl<K: Hash + Eq> Palette<K> {
+    pub fn new(background: Rgb) -> Self {
+        Palette {
+            colors: vec![background],
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Adds the color of `kind`. A palette holds at most 256 colors, background included.
+    pub fn with(mut self, kind: K, color: Rgb) -> Self {
+        let idx = match self.colors.iter().position(|c| *c == color) {
+            Some(idx) => idx,
+            None => {
+                assert!(self.colors.len() < 256, "Too many colors in the palette");
+                self.colors.push(color);
+                self.colors.len() - 1
+            }
+        };
+        self.indices.insert(kind, idx as u8);
+        self
+    }
+
+    fn index_of(&self, kind: &K) -> u8 {
+        *self.indices.get(kind).unwrap_or(&0)
+    }
+
+    pub fn color_of(&self, kind: &K) -> Rgb {
+        self.colors[self.index_of(kind) as usize]
+    }
+
+    fn flat_colors(&self) -> Vec<u8> {
+        self.colors.iter().flatten().copied().collect()
+    }
+}
+
+/// A picture, as indices in the colors of a `Palette`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn blank(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    fn fill_cell(&mut self, x: usize, y: usize, scale: usize, idx: u8) {
+        for py in y * scale..(y + 1) * scale {
+            let row = py * self.width;
+            self.pixels[row + x * scale..row + (x + 1) * scale].fill(idx);
+        }
+    }
+
+    /// Draws a dense grid, given as rows of cells. Short rows are padded with the background.
+    pub fn from_grid<K: Hash + Eq>(grid: &[Vec<K>], palette: &Palette<K>, scale: usize) -> Self {
+        let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut image = Image::blank(width * scale, grid.len() * scale);
+        for (y, row) in grid.iter().enumerate() {
+            for (x, kind) in row.iter().enumerate() {
+                image.fill_cell(x, y, scale, palette.index_of(kind));
+            }
+        }
+        image
+    }
+
+    /// Draws the cells that fall in `bbox`, given as `(min_x, min_y, max_x, max_y)` with `y`
+    /// growing downwards.
+    pub fn from_cells<K: Hash + Eq>(
+        cells: &HashMap<(i64, i64), K>,
+        bbox: (i64, i64, i64, i64),
+        palette: &Palette<K>,
+        scale: usize,
+    ) -> Self {
+        let (min_x, min_y, max_x, max_y) = bbox;
+        let width = (max_x - min_x + 1).max(0) as usize;
+        let height = (max_y - min_y + 1).max(0) as usize;
+        let mut image = Image::blank(width * scale, height * scale);
+        for ((x, y), kind) in cells.iter() {
+            if *x < min_x || *x > max_x || *y < min_y || *y > max_y {
+                continue;
+            }
+            image.fill_cell(
+                (x - min_x) as usize,
+                (y - min_y) as usize,
+                scale,
+                palette.index_of(kind),
+            );
+        }
+        image
+    }
+
+    /// The color of the pixel at `(x, y)`.
+    pub fn pixel<K: Hash + Eq>(&self, x: usize, y: usize, palette: &Palette<K>) -> Rgb {
+        palette.colors[self.pixels[y * self.width + x] as usize]
+    }
+}
+
+pub fn write_png<K: Hash + Eq, P: AsRef<Path>>(
+    path: P,
+    image: &Image,
+    palette: &Palette<K>,
+) -> Result<(), Box<dyn Error>> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.flat_colors());
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    Ok(())
+}
+
+/// Writes `images` as a looping animation, each one shown for `delay`. They must all have the
+/// same size.
+pub fn write_gif<K: Hash + Eq, P: AsRef<Path>>(
+    path: P,
+    images: &[Image],
+    palette: &Palette<K>,
+    delay: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let first = images.first().ok_or("No image to write")?;
+    if images
+        .iter()
+        .any(|i| i.width != first.width || i.height != first.height)
+    {
+        return Err("All the images of a GIF should have the same size".into());
+    }
+    // GIF sizes are on 16 bits
+    let (width, height) = match (u16::try_from(first.width), u16::try_from(first.height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A GIF cannot be {}x{} pixels, {} at most on each side",
+                    first.width,
+                    first.height,
+                    u16::MAX
+                ),
+            )
+            .into())
+        }
+    };
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &palette.flat_colors())?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    // GIF delays are in hundredths of seconds, and most viewers ignore anything below 2
+    let delay = (delay.as_millis() / 10).clamp(2, u16::MAX as u128) as u16;
+    for image in images {
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, image.pixels.clone(), None);
+        frame.delay = delay;
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+/// Draws the frames of `range` on a common canvas, so that they can be animated.
+pub fn recorder_images(
+    recorder: &Recorder,
+    range: Range<usize>,
+    palette: &Palette<char>,
+    scale: usize,
+) -> Vec<Image> {
+    let bbox = recorder.bounding_box().unwrap_or((0, 0, -1, -1));
+    let range = viz::clamp(range, recorder.len());
+    let mut images: Vec<Image> = range
+        .filter_map(|idx| recorder.frame(idx))
+        .map(|frame| match frame {
+            Frame::Grid(rows) => {
+                let grid: Vec<Vec<char>> = rows.iter().map(|row| row.chars().collect()).collect();
+                Image::from_grid(&grid, palette, scale)
+            }
+            Frame::Cells(cells) => Image::from_cells(cells, bbox, palette, scale),
+        })
+        .collect();
+
+    // grids can change size between frames, pad them to the biggest one
+    let width = images.iter().map(|i| i.width).max().unwrap_or(0);
+    let height = images.iter().map(|i| i.height).max().unwrap_or(0);
+    for image in images.iter_mut() {
+        if image.width != width || image.height != height {
+            let mut padded = Image::blank(width, height);
+            for y in 0..image.height {
+                padded.pixels[y * width..y * width + image.width]
+                    .copy_from_slice(&image.pixels[y * image.width..(y + 1) * image.width]);
+            }
+            *image = padded;
+        }
+    }
+    images
+}
+
+/// Exports the frames recorded for `playback` to its `render` path, if any: an animated GIF when
+/// the path ends with `.gif`, PNG images otherwise. When there are several frames, each PNG is
+/// numbered, e.g. `out-00012.png`.
+pub fn export(
+    recorder: &Recorder,
+    playback: &Playback,
+    palette: &Palette<char>,
+) -> Result<(), Box<dyn Error>> {
+    let path = match &playback.render {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let images = recorder_images(recorder, playback.frames.clone(), palette, playback.scale);
+    if images.is_empty() {
+        println!(
+            "No frame in {:?} to export, only {} recorded",
+            playback.frames,
+            recorder.len()
+        );
+        return Ok(());
+    }
+
+    let is_gif = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if is_gif {
+        return write_gif(
+            path,
+            &images,
+            palette,
+            Duration::from_secs_f64(1.0 / playback.fps),
+        );
+    }
+
+    if images.len() == 1 {
+        return write_png(path, &images[0], palette);
+    }
+    let first = playback.frames.start;
+    for (i, image) in images.iter().enumerate() {
+        write_png(numbered(path, first + i), image, palette)?;
+    }
+    Ok(())
+}
+
+fn numbered(path: &Path, idx: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{:05}.{}", stem, idx, ext.to_string_lossy()),
+        None => format!("{}-{:05}", stem, idx),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    const BLACK: Rgb = [0, 0, 0];
+    const WHITE: Rgb = [255, 255, 255];
+    const RED: Rgb = [255, 0, 0];
+
+    fn palette() -> Palette<char> {
+        Palette::new(BLACK).with('#', WHITE).with('o', RED)
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = palette().with('O', WHITE);
+        assert_eq!(palette.color_of(&'#'), WHITE);
+        assert_eq!(palette.color_of(&'O'), WHITE);
+        assert_eq!(palette.color_of(&'?'), BLACK);
+        // same color, same index
+        assert_eq!(palette.flat_colors().len(), 3 * 3);
+    }
+
+    #[test]
+    fn test_images() {
+        let grid = vec![vec!['#', 'o'], vec!['.']];
+        let image = Image::from_grid(&grid, &palette(), 2);
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.pixel(1, 1, &palette()), WHITE);
+        assert_eq!(image.pixel(3, 0, &palette()), RED);
+        assert_eq!(image.pixel(3, 3, &palette()), BLACK);
+
+        let cells = HashMap::from([((10, -1), 'o'), ((12, 0), '#'), ((50, 50), '#')]);
+        let image = Image::from_cells(&cells, (10, -1, 12, 0), &palette(), 1);
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixel(0, 0, &palette()), RED);
+        assert_eq!(image.pixel(2, 1, &palette()), WHITE);
+        assert_eq!(image.pixel(1, 1, &palette()), BLACK);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut recorder = Recorder::new();
+        recorder.push_cells("a".to_string(), [((0, 0), '#')]);
+        recorder.push_cells("b".to_string(), [((3, 2), 'o')]);
+
+        let images = recorder_images(&recorder, 0..10, &palette(), 3);
+        assert_eq!(images.len(), 2);
+        assert!(images.iter().all(|i| i.width == 12 && i.height == 9));
+
+        let dir = env::temp_dir().join(format!("utils-render-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut playback = Playback::from_args(["--render", dir.join("out.gif").to_str().unwrap()])
+            .unwrap()
+            .unwrap();
+        export(&recorder, &playback, &palette()).unwrap();
+        assert!(fs::read(dir.join("out.gif"))
+            .unwrap()
+            .starts_with(b"GIF89a"));
+
+        playback.render = Some(dir.join("out.png"));
+        export(&recorder, &playback, &palette()).unwrap();
+        for name in ["out-00000.png", "out-00001.png"] {
+            assert!(fs::read(dir.join(name)).unwrap().starts_with(b"\x89PNG"));
+        }
+
+        // too wide for a GIF, and nothing is written
+        let path = dir.join("wide.gif");
+        let error = write_gif(
+            &path,
+            &[Image::blank(70000, 1)],
+            &palette(),
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::InvalidInput)
+        );
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
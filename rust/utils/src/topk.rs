@@ -0,0 +1,151 @@
+//! Keeps the biggest items of a stream, without storing the whole stream.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The `k` biggest items pushed so far.
+///
+/// They are kept in a min-heap of at most `k` items, so that the smallest one is dropped first
+/// when a bigger item comes: pushing costs O(log k) and the memory used does not grow with the
+/// stream.
+///
+/// ```
+/// use utils::topk::TopK;
+///
+/// let mut top = TopK::new(3);
+/// top.extend([5, 1, 8, 3, 9, 2]);
+/// assert_eq!(top.min(), Some(&5));
+/// assert_eq!(top.into_sorted_vec(), [9, 8, 5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TopK<T: Ord> {
+    k: usize,
+    heap: BinaryHeap<Reverse<T>>,
+}
+
+impl<T: Ord> TopK<T> {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            heap: BinaryHeap::with_capacity(k),
+        }
+    }
+
+    /// Keeps `item` if it is among the `k` biggest so far. Among equal items, the first ones
+    /// pushed are kept.
+    pub fn push(&mut self, item: T) {
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(item));
+        } else if let Some(mut smallest) = self.heap.peek_mut() {
+            if item > smallest.0 {
+                *smallest = Reverse(item);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The smallest of the items kept, the one a new item has to beat once there are `k`.
+    pub fn min(&self) -> Option<&T> {
+        self.heap.peek().map(|smallest| &smallest.0)
+    }
+
+    /// The items kept, biggest first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        // the heap sorts its `Reverse` items in increasing order, so biggest first
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|item| item.0)
+            .collect()
+    }
+}
+
+impl<T: Ord> Extend<T> for TopK<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+/// The `k` biggest items of `iter`, biggest first.
+pub fn top_k<T: Ord, I: IntoIterator<Item = T>>(iter: I, k: usize) -> Vec<T> {
+    let mut top = TopK::new(k);
+    top.extend(iter);
+    top.into_sorted_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k() {
+        assert_eq!(top_k([4, 1, 7, 7, 3], 2), [7, 7]);
+        assert_eq!(top_k([4, 1, 7, 7, 3], 10), [7, 7, 4, 3, 1]);
+        assert_eq!(top_k([4, 1, 7], 0), Vec::<i32>::new());
+        assert_eq!(top_k(Vec::<i32>::new(), 3), Vec::<i32>::new());
+
+        // matches sorting the whole stream
+        let stream: Vec<u64> = (0..1000).map(|i| (i * 7919) % 1009).collect();
+        let mut sorted = stream.clone();
+        sorted.sort_by(|a, b| b.cmp(a));
+        assert_eq!(top_k(stream.iter().copied(), 25), sorted[..25]);
+    }
+
+    #[test]
+    fn test_accumulator() {
+        let mut top = TopK::new(2);
+        assert!(top.is_empty());
+        assert_eq!(top.min(), None);
+
+        top.push((3, "c"));
+        top.push((1, "a"));
+        assert_eq!(top.len(), 2);
+        assert_eq!(top.min(), Some(&(1, "a")));
+
+        // too small to get in
+        top.push((0, "z"));
+        assert_eq!(top.min(), Some(&(1, "a")));
+
+        top.push((2, "b"));
+        assert_eq!(top.len(), 2);
+        assert_eq!(top.clone().into_sorted_vec(), [(3, "c"), (2, "b")]);
+
+        // the first of equal items stays
+        let mut top = TopK::new(1);
+        top.extend([(5, 0), (5, 1)].map(|(v, idx)| Ranked(v, idx)));
+        assert_eq!(top.into_sorted_vec()[0].1, 0);
+    }
+
+    /// Compares on the value only.
+    #[derive(Debug)]
+    struct Ranked(u32, usize);
+
+    impl PartialEq for Ranked {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Ranked {}
+
+    impl PartialOrd for Ranked {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Ranked {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Records the successive states of a simulation, to replay them in the terminal afterwards.
+//!
+//! Days that support it accept the following flags:
+//! - `--play`: replay the frames as an animation
+//! - `--step`: replay the frames one by one, reading commands on stdin
+//! - `--dump`: print the frames one after the other
+//! - `--frames <from>..<to>`: only replay or dump these frames (`<from>..` and `..<to>` work too)
+//! - `--fps <n>`: speed of `--play`, 10 frames per second by default
+//! - `--every <n>`: only record one frame every `n`, for long simulations
+//! - `--render <path>`: export the frames as an animated GIF, or as PNG images, see
+//!   `utils::render`
+//! - `--scale <n>`: size in pixels of a cell in the exported images, 4 by default
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// One state of the simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// Rows of text, rendered as is
+    Grid(Vec<String>),
+    /// Only the interesting cells, `(x, y)` with `y` growing downwards. They are rendered on the
+    /// bounding box of all the recorded cells, so that the view does not move between frames.
+    Cells(HashMap<(i64, i64), char>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Recorded {
+    label: String,
+    frame: Frame,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recorder {
+    frames: Vec<Recorded>,
+    every: usize,
+    pushed: usize,
+    background: char,
+    // bounding box of the cells recorded so far, kept up to date so that rendering a frame
+    // does not go through all the others
+    bbox: Option<(i64, i64, i64, i64)>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder {
+            frames: Vec::new(),
+            every: 1,
+            pushed: 0,
+            background: '.',
+            bbox: None,
+        }
+    }
+
+    /// Only keeps one frame every `every` pushed frames.
+    pub fn every(mut self, every: usize) -> Self {
+        self.every = every.max(1);
+        self
+    }
+
+    /// Character used for the cells missing from a `Frame::Cells`.
+    pub fn background(mut self, background: char) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Records the frame built by `frame`. It is only called when the frame is kept, so that
+    /// long simulations only pay for the frames they keep.
+    pub fn record<F>(&mut self, frame: F)
+    where
+        F: FnOnce() -> (String, Frame),
+    {
+        self.pushed += 1;
+        if !(self.pushed - 1).is_multiple_of(self.every) {
+            return;
+        }
+        let (label, frame) = frame();
+        if let Frame::Cells(cells) = &frame {
+            for (x, y) in cells.keys() {
+                self.bbox = Some(match self.bbox {
+                    None => (*x, *y, *x, *y),
+                    Some((min_x, min_y, max_x, max_y)) => {
+                        (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+                    }
+                });
+            }
+        }
+        self.frames.push(Recorded { label, frame });
+    }
+
+    pub fn push(&mut self, label: String, frame: Frame) {
+        self.record(|| (label, frame));
+    }
+
+    pub fn push_grid<I, S>(&mut self, label: String, rows: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.push(
+            label,
+            Frame::Grid(rows.into_iter().map(|row| row.into()).collect()),
+        );
+    }
+
+    pub fn push_cells<I>(&mut self, label: String, cells: I)
+    where
+        I: IntoIterator<Item = ((i64, i64), char)>,
+    {
+        self.push(label, Frame::Cells(cells.into_iter().collect()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn frame(&self, idx: usize) -> Option<&Frame> {
+        self.frames.get(idx).map(|recorded| &recorded.frame)
+    }
+
+    pub fn label(&self, idx: usize) -> Option<&str> {
+        self.frames.get(idx).map(|recorded| recorded.label.as_str())
+    }
+
+    /// `(min_x, min_y, max_x, max_y)` of all the cells of all the frames
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        self.bbox
+    }
+
+    /// Renders the frame at `idx` as text, without its label.
+    pub fn render(&self, idx: usize) -> Option<String> {
+        let recorded = self.frames.get(idx)?;
+        match &recorded.frame {
+            Frame::Grid(rows) => Some(rows.join("\n")),
+            Frame::Cells(cells) => {
+                let (min_x, min_y, max_x, max_y) = match self.bounding_box() {
+                    Some(bbox) => bbox,
+                    None => return Some(String::new()),
+                };
+                let mut rows = Vec::new();
+                for y in min_y..=max_y {
+                    let row: String = (min_x..=max_x)
+                        .map(|x| *cells.get(&(x, y)).unwrap_or(&self.background))
+                        .collect();
+                    rows.push(row);
+                }
+                Some(rows.join("\n"))
+            }
+        }
+    }
+
+    fn write_frame<W: Write>(&self, idx: usize, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "== Frame {}/{}: {}",
+            idx,
+            self.len() - 1,
+            self.frames[idx].label
+        )?;
+        writeln!(out, "{}", self.render(idx).unwrap_or_default())
+    }
+
+    /// Writes the frames of `range` one after the other.
+    pub fn dump<W: Write>(&self, range: Range<usize>, out: &mut W) -> io::Result<()> {
+        for idx in clamp(range, self.len()) {
+            self.write_frame(idx, out)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Replays the recorded frames on stdout, as described by `playback`.
+    pub fn replay(&self, playback: &Playback) -> io::Result<()> {
+        if self.is_empty() {
+            println!("Nothing recorded");
+            return Ok(());
+        }
+
+        let mode = match playback.mode {
+            Some(mode) => mode,
+            None => return Ok(()),
+        };
+        let range = clamp(playback.frames.clone(), self.len());
+        if range.is_empty() {
+            println!(
+                "No frame in {:?}, only {} recorded",
+                playback.frames,
+                self.len()
+            );
+            return Ok(());
+        }
+        match mode {
+            Mode::Dump => self.dump(range, &mut io::stdout()),
+            Mode::Play => self.play(range, playback.fps),
+            Mode::Step => self.step(range, playback.fps, &mut io::stdin().lock()),
+        }
+    }
+
+    fn play(&self, range: Range<usize>, fps: f64) -> io::Result<()> {
+        let delay = Duration::from_secs_f64(1.0 / fps);
+        let mut out = io::stdout();
+        for idx in range {
+            // clear the screen and go back to the top left corner
+            write!(out, "\x1b[2J\x1b[H")?;
+            self.write_frame(idx, &mut out)?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    fn step<R: BufRead>(&self, range: Range<usize>, fps: f64, input: &mut R) -> io::Result<()> {
+        let mut out = io::stdout();
+        let mut idx = range.start;
+        loop {
+            write!(out, "\x1b[2J\x1b[H")?;
+            self.write_frame(idx, &mut out)?;
+            write!(
+                out,
+                "[enter] next, [b] back, [g <n>] go to frame n, [p] play from here, [q] quit > "
+            )?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match StepCommand::parse(line.trim()) {
+                StepCommand::Next => idx = (idx + 1).min(range.end - 1),
+                StepCommand::Back => idx = idx.saturating_sub(1).max(range.start),
+                StepCommand::Goto(target) => idx = target.clamp(range.start, range.end - 1),
+                StepCommand::Play => return self.play(idx..range.end, fps),
+                StepCommand::Quit => return Ok(()),
+                StepCommand::Unknown => (),
+            }
+        }
+    }
+}
+
+pub(crate) fn clamp(range: Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum StepCommand {
+    Next,
+    Back,
+    Goto(usize),
+    Play,
+    Quit,
+    Unknown,
+}
+
+impl StepCommand {
+    fn parse(command: &str) -> Self {
+        match command.split_once(' ') {
+            Some(("g", n)) => match n.trim().parse::<usize>() {
+                Ok(n) => StepCommand::Goto(n),
+                Err(_) => StepCommand::Unknown,
+            },
+            _ => match command {
+                "" | "n" => StepCommand::Next,
+                "b" => StepCommand::Back,
+                "p" => StepCommand::Play,
+                "q" => StepCommand::Quit,
+                _ => StepCommand::Unknown,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Play,
+    Step,
+    Dump,
+}
+
+/// How to replay a `Recorder`, usually read from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playback {
+    /// How to replay the frames in the terminal, if at all
+    pub mode: Option<Mode>,
+    pub frames: Range<usize>,
+    pub fps: f64,
+    pub every: usize,
+    /// Where to export the frames as images
+    pub render: Option<PathBuf>,
+    pub scale: usize,
+}
+
+impl Playback {
+    /// Reads the flags described in the module documentation. Returns `None` when none of
+    /// `--play`, `--step`, `--dump` or `--render` is given, in which case nothing should be
+    /// recorded.
+    pub fn from_args<I, S>(args: I) -> Result<Option<Self>, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut mode = None;
+        let mut frames = 0..usize::MAX;
+        let mut fps = 10.0;
+        let mut every = 1;
+        let mut render = None;
+        let mut scale = 4;
+
+        let args: Vec<String> = args.into_iter().map(|a| a.as_ref().to_string()).collect();
+        let mut it = args.iter();
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "--play" => mode = Some(Mode::Play),
+                "--step" => mode = Some(Mode::Step),
+                "--dump" => mode = Some(Mode::Dump),
+                "--frames" => frames = parse_range(it.next())?,
+                "--fps" => {
+                    fps = parse_value(it.next(), "--fps")?;
+                    if fps <= 0.0 {
+                        return Err("--fps should be positive".to_string());
+                    }
+                }
+                "--every" => every = parse_value(it.next(), "--every")?,
+                "--render" => render = Some(parse_value(it.next(), "--render")?),
+                "--scale" => {
+                    scale = parse_value(it.next(), "--scale")?;
+                    if scale == 0 {
+                        return Err("--scale should be positive".to_string());
+                    }
+                }
+                // other flags belong to the day
+                _ => (),
+            }
+        }
+
+        if mode.is_none() && render.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Playback {
+            mode,
+            frames,
+            fps,
+            every,
+            render,
+            scale,
+        }))
+    }
+
+    /// A recorder that keeps the frames needed by this playback.
+    pub fn recorder(&self) -> Recorder {
+        Recorder::new().every(self.every)
+    }
+
+    /// The same playback, rendering to `<stem>-part<part>.<ext>` so that the recordings of
+    /// the parts do not overwrite each other.
+    pub fn for_part(&self, part: u32) -> Self {
+        let mut playback = self.clone();
+        if let Some(path) = &self.render {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(ext) => format!("{}-part{}.{}", stem, part, ext.to_string_lossy()),
+                None => format!("{}-part{}", stem, part),
+            };
+            playback.render = Some(path.with_file_name(name));
+        }
+        playback
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T, String> {
+    let value = value.ok_or(format!("Missing value for {}", flag))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Bad value for {}: {}", flag, value))
+}
+
+fn parse_range(value: Option<&String>) -> Result<Range<usize>, String> {
+    let value = value.ok_or("Missing value for --frames")?;
+    let bad_range = || format!("Bad frame range {}, expected <from>..<to>", value);
+    let (from, to) = value.split_once("..").ok_or_else(bad_range)?;
+    let from = match from {
+        "" => 0,
+        n => n.parse::<usize>().map_err(|_| bad_range())?,
+    };
+    let to = match to {
+        "" => usize::MAX,
+        n => n.parse::<usize>().map_err(|_| bad_range())?,
+    };
+    if from > to {
+        return Err(bad_range());
+    }
+    Ok(from..to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_cells_on_common_bbox() {
+        let mut recorder = Recorder::new();
+        recorder.push_cells("first".to_string(), [((0, 0), '#')]);
+        recorder.push_cells("second".to_string(), [((2, 1), 'o'), ((1, 0), '+')]);
+        recorder.push_grid("grid".to_string(), ["ab", "cd"]);
+
+        assert_eq!(recorder.len(), 3);
+        assert_eq!(recorder.render(0).unwrap(), "#..\n...");
+        assert_eq!(recorder.render(1).unwrap(), ".+.\n..o");
+        assert_eq!(recorder.render(2).unwrap(), "ab\ncd");
+        assert_eq!(recorder.label(1), Some("second"));
+        assert_eq!(recorder.render(3), None);
+    }
+
+    #[test]
+    fn test_bounding_box_grows_with_the_kept_frames() {
+        let mut recorder = Recorder::new().every(2);
+        assert_eq!(recorder.bounding_box(), None);
+        recorder.push_cells("kept".to_string(), [((1, 1), '#')]);
+        assert_eq!(recorder.bounding_box(), Some((1, 1, 1, 1)));
+        // not kept, so not rendered
+        recorder.push_cells("skipped".to_string(), [((-5, -5), '#')]);
+        recorder.push_cells("kept".to_string(), [((0, 3), 'o'), ((2, 2), 'o')]);
+        assert_eq!(recorder.bounding_box(), Some((0, 1, 2, 3)));
+        assert_eq!(recorder.render(0).unwrap(), ".#.\n...\n...");
+    }
+
+    #[test]
+    fn test_every_and_dump() {
+        let mut recorder = Recorder::new().every(2);
+        for i in 0..5 {
+            recorder.push_grid(format!("step {}", i), [i.to_string()]);
+        }
+        assert_eq!(recorder.len(), 3);
+
+        let mut out = Vec::new();
+        recorder.dump(1..10, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "== Frame 1/2: step 2\n2\n\n== Frame 2/2: step 4\n4\n\n"
+        );
+    }
+
+    #[test]
+    fn test_from_args() {
+        assert_eq!(Playback::from_args(["d14"]), Ok(None));
+        assert_eq!(
+            Playback::from_args(["d14", "--step", "--frames", "3..", "--every", "5"]),
+            Ok(Some(Playback {
+                mode: Some(Mode::Step),
+                frames: 3..usize::MAX,
+                fps: 10.0,
+                every: 5,
+                render: None,
+                scale: 4,
+            }))
+        );
+        assert_eq!(
+            Playback::from_args(["--render", "out.gif", "--scale", "2"]),
+            Ok(Some(Playback {
+                mode: None,
+                frames: 0..usize::MAX,
+                fps: 10.0,
+                every: 1,
+                render: Some(PathBuf::from("out.gif")),
+                scale: 2,
+            }))
+        );
+        assert_eq!(
+            Playback::from_args(["--render", "/tmp/out.gif"])
+                .unwrap()
+                .unwrap()
+                .for_part(2)
+                .render,
+            Some(PathBuf::from("/tmp/out-part2.gif"))
+        );
+        assert!(Playback::from_args(["--dump", "--frames", "5..2"]).is_err());
+        assert!(Playback::from_args(["--play", "--fps"]).is_err());
+    }
+
+    #[test]
+    fn test_step_commands() {
+        assert_eq!(StepCommand::parse(""), StepCommand::Next);
+        assert_eq!(StepCommand::parse("g 12"), StepCommand::Goto(12));
+        assert_eq!(StepCommand::parse("g x"), StepCommand::Unknown);
+        assert_eq!(StepCommand::parse("q"), StepCommand::Quit);
+    }
+}
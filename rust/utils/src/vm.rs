@@ -0,0 +1,506 @@
+//! A small register machine, for the puzzles that run made-up assembly.
+//!
+//! Nothing is hard-coded: an `InstructionSet` declares the registers and, for each opcode, its
+//! number of operands, how many cycles it takes and what it does. A program is parsed against
+//! the set, then run by a `Machine`, which calls a hook during every cycle and can stop on
+//! breakpoints.
+//!
+//! ```
+//! use utils::vm::{InstructionSet, Jump, Stop};
+//!
+//! let set = InstructionSet::new(&["a", "b"])
+//!     .with("inc", 1, 1, |regs, ops| {
+//!         regs.write(&ops[0], regs.read(&ops[0]) + 1);
+//!         Jump::Next
+//!     })
+//!     .with("add", 2, 3, |regs, ops| {
+//!         regs.write(&ops[0], regs.read(&ops[0]) + regs.read(&ops[1]));
+//!         Jump::Next
+//!     });
+//!
+//! let mut machine = set.load("inc a\nadd b 10\nadd b a").unwrap();
+//! assert_eq!(machine.run(|_, _| ()), Stop::Halted);
+//! assert_eq!(machine.registers.get("b"), Some(11));
+//! assert_eq!(machine.cycle(), 7);
+//! ```
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// Either a register, by index, or an immediate value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(usize),
+    Value(i64),
+}
+
+/// Where to go once an instruction is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jump {
+    Next,
+    /// Offset from the current instruction. Jumping outside of the program halts the machine.
+    Relative(i64),
+}
+
+/// What an instruction does, applied at the end of its last cycle.
+pub type Exec = fn(&mut Registers, &[Operand]) -> Jump;
+
+#[derive(Debug, Clone)]
+struct InstructionDef {
+    name: String,
+    operands: usize,
+    cycles: u64,
+    exec: Exec,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    def: usize,
+    pub operands: Vec<Operand>,
+    /// Line of the program, starting at 1
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownOpcode {
+        line: usize,
+        opcode: String,
+    },
+    OperandCount {
+        line: usize,
+        opcode: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Neither a register nor a number
+    BadOperand {
+        line: usize,
+        operand: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownOpcode { line, opcode } => {
+                write!(f, "Line {}: unknown opcode {}", line, opcode)
+            }
+            ParseError::OperandCount {
+                line,
+                opcode,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {}: {} takes {} operands, found {}",
+                line, opcode, expected, found
+            ),
+            ParseError::BadOperand { line, operand } => {
+                write!(f, "Line {}: bad operand {}", line, operand)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// The registers of a machine, all starting at 0. They can be indexed by number, in the order
+/// they were declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registers {
+    names: Vec<String>,
+    values: Vec<i64>,
+}
+
+impl Registers {
+    fn new(names: Vec<String>) -> Self {
+        let values = vec![0; names.len()];
+        Registers { names, values }
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<i64> {
+        self.index_of(name).map(|idx| self.values[idx])
+    }
+
+    /// Sets the register `name`. Returns false if there is no such register.
+    pub fn set(&mut self, name: &str, value: i64) -> bool {
+        match self.index_of(name) {
+            Some(idx) => {
+                self.values[idx] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn read(&self, operand: &Operand) -> i64 {
+        match operand {
+            Operand::Register(idx) => self.values[*idx],
+            Operand::Value(v) => *v,
+        }
+    }
+
+    /// Writes to a register operand. Writing to an immediate value does nothing.
+    pub fn write(&mut self, operand: &Operand, value: i64) {
+        if let Operand::Register(idx) = operand {
+            self.values[*idx] = value;
+        }
+    }
+}
+
+impl Index<usize> for Registers {
+    type Output = i64;
+
+    fn index(&self, idx: usize) -> &i64 {
+        &self.values[idx]
+    }
+}
+
+impl IndexMut<usize> for Registers {
+    fn index_mut(&mut self, idx: usize) -> &mut i64 {
+        &mut self.values[idx]
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers: Vec<String> = self
+            .names
+            .iter()
+            .zip(self.values.iter())
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        write!(f, "{}", registers.join(" "))
+    }
+}
+
+/// The registers and the instructions of a machine.
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    registers: Vec<String>,
+    defs: Vec<InstructionDef>,
+}
+
+impl InstructionSet {
+    pub fn new(registers: &[&str]) -> Self {
+        InstructionSet {
+            registers: registers.iter().map(|r| r.to_string()).collect(),
+            defs: Vec::new(),
+        }
+    }
+
+    /// Adds the instruction `name`, taking `operands` operands and `cycles` cycles. Its effect
+    /// is applied at the end of the last cycle.
+    pub fn with(mut self, name: &str, operands: usize, cycles: u64, exec: Exec) -> Self {
+        assert!(cycles > 0, "An instruction takes at least one cycle");
+        self.defs.retain(|def| def.name != name);
+        self.defs.push(InstructionDef {
+            name: name.to_string(),
+            operands,
+            cycles,
+            exec,
+        });
+        self
+    }
+
+    /// Parses a program, one instruction per line. Blank lines are skipped.
+    pub fn parse(&self, program: &str) -> Result<Vec<Instruction>, ParseError> {
+        let mut instructions = Vec::new();
+        for (idx, text) in program.lines().enumerate() {
+            let line = idx + 1;
+            let mut tokens = text.split_whitespace();
+            let opcode = match tokens.next() {
+                Some(opcode) => opcode,
+                None => continue,
+            };
+            let def = self.defs.iter().position(|d| d.name == opcode).ok_or(
+                ParseError::UnknownOpcode {
+                    line,
+                    opcode: opcode.to_string(),
+                },
+            )?;
+
+            let operands = tokens
+                .map(|token| self.parse_operand(token, line))
+                .collect::<Result<Vec<Operand>, ParseError>>()?;
+            if operands.len() != self.defs[def].operands {
+                return Err(ParseError::OperandCount {
+                    line,
+                    opcode: opcode.to_string(),
+                    expected: self.defs[def].operands,
+                    found: operands.len(),
+                });
+            }
+
+            instructions.push(Instruction {
+                def,
+                operands,
+                line,
+            });
+        }
+        Ok(instructions)
+    }
+
+    fn parse_operand(&self, token: &str, line: usize) -> Result<Operand, ParseError> {
+        if let Some(idx) = self.registers.iter().position(|r| r == token) {
+            return Ok(Operand::Register(idx));
+        }
+        token
+            .parse::<i64>()
+            .map(Operand::Value)
+            .map_err(|_| ParseError::BadOperand {
+                line,
+                operand: token.to_string(),
+            })
+    }
+
+    /// Parses `program` and loads it in a new machine.
+    pub fn load(&self, program: &str) -> Result<Machine, ParseError> {
+        Ok(Machine::new(self.clone(), self.parse(program)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// Stops before the cycle with this number, the first cycle being 1
+    Cycle(u64),
+    /// Stops before starting the instruction at this index of the program
+    Instruction(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    Breakpoint(Breakpoint),
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    set: InstructionSet,
+    program: Vec<Instruction>,
+    pub registers: Registers,
+    pc: usize,
+    cycle: u64,
+    // cycles already spent on the current instruction
+    progress: u64,
+    breakpoints: HashSet<Breakpoint>,
+    // cycles done when `run` last stopped at a breakpoint, so that running again resumes
+    stopped_at: Option<u64>,
+}
+
+impl Machine {
+    pub fn new(set: InstructionSet, program: Vec<Instruction>) -> Self {
+        let registers = Registers::new(set.registers.clone());
+        Machine {
+            set,
+            program,
+            registers,
+            pc: 0,
+            cycle: 0,
+            progress: 0,
+            breakpoints: HashSet::new(),
+            stopped_at: None,
+        }
+    }
+
+    /// Number of cycles done so far.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Index of the current instruction.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.remove(&breakpoint);
+    }
+
+    fn breakpoint(&self) -> Option<Breakpoint> {
+        let cycle = Breakpoint::Cycle(self.cycle + 1);
+        let instruction = Breakpoint::Instruction(self.pc);
+        if self.breakpoints.contains(&cycle) {
+            Some(cycle)
+        } else if self.progress == 0 && self.breakpoints.contains(&instruction) {
+            Some(instruction)
+        } else {
+            None
+        }
+    }
+
+    /// Runs until the program ends or a breakpoint is hit. Running again resumes from the
+    /// breakpoint.
+    ///
+    /// `hook` is called during each cycle with its number and the registers, which do not
+    /// include yet the effect of an instruction ending in this cycle.
+    pub fn run<H>(&mut self, mut hook: H) -> Stop
+    where
+        H: FnMut(u64, &Registers),
+    {
+        while !self.is_halted() {
+            if self.stopped_at != Some(self.cycle) {
+                if let Some(breakpoint) = self.breakpoint() {
+                    self.stopped_at = Some(self.cycle);
+                    return Stop::Breakpoint(breakpoint);
+                }
+            }
+
+            self.cycle += 1;
+            self.progress += 1;
+            hook(self.cycle, &self.registers);
+
+            let instruction = &self.program[self.pc];
+            let def = &self.set.defs[instruction.def];
+            if self.progress < def.cycles {
+                continue;
+            }
+            self.progress = 0;
+            match (def.exec)(&mut self.registers, &instruction.operands) {
+                Jump::Next => self.pc += 1,
+                Jump::Relative(offset) => {
+                    // out of the program on either side, which halts the machine
+                    self.pc = usize::try_from(self.pc as i64 + offset).unwrap_or(usize::MAX);
+                }
+            }
+        }
+        Stop::Halted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set() -> InstructionSet {
+        InstructionSet::new(&["a", "b"])
+            .with("noop", 0, 1, |_, _| Jump::Next)
+            .with("cpy", 2, 2, |regs, ops| {
+                regs.write(&ops[1], regs.read(&ops[0]));
+                Jump::Next
+            })
+            .with("dec", 1, 1, |regs, ops| {
+                regs.write(&ops[0], regs.read(&ops[0]) - 1);
+                Jump::Next
+            })
+            .with("jnz", 2, 1, |regs, ops| match regs.read(&ops[0]) {
+                0 => Jump::Next,
+                _ => Jump::Relative(regs.read(&ops[1])),
+            })
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            set().parse("noop\n\nmul a 2").unwrap_err(),
+            ParseError::UnknownOpcode {
+                line: 3,
+                opcode: "mul".to_string()
+            }
+        );
+        assert_eq!(
+            set().parse("cpy 1").unwrap_err(),
+            ParseError::OperandCount {
+                line: 1,
+                opcode: "cpy".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            set().parse("noop\ndec c").unwrap_err(),
+            ParseError::BadOperand {
+                line: 2,
+                operand: "c".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_hooks_and_jumps() {
+        let mut machine = set()
+            .load("cpy 3 a\ndec a\nnoop\njnz a -2\ncpy a b")
+            .unwrap();
+        let mut seen = Vec::new();
+        assert_eq!(
+            machine.run(|cycle, regs| seen.push((cycle, regs.get("a").unwrap()))),
+            Stop::Halted
+        );
+        // the hook sees the value during the cycle, before the instruction ends
+        assert_eq!(&seen[..4], [(1, 0), (2, 0), (3, 3), (4, 2)]);
+        assert_eq!(machine.cycle(), 2 + 3 * 3 + 2);
+        assert_eq!(machine.registers.get("a"), Some(0));
+        assert!(machine.is_halted());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut machine = set()
+            .load("cpy 3 a\ndec a\nnoop\njnz a -2\ncpy 7 b")
+            .unwrap();
+        machine.add_breakpoint(Breakpoint::Cycle(2));
+        machine.add_breakpoint(Breakpoint::Instruction(1));
+
+        // in the middle of cpy
+        assert_eq!(
+            machine.run(|_, _| ()),
+            Stop::Breakpoint(Breakpoint::Cycle(2))
+        );
+        assert_eq!((machine.cycle(), machine.pc()), (1, 0));
+        assert_eq!(
+            machine.run(|_, _| ()),
+            Stop::Breakpoint(Breakpoint::Instruction(1))
+        );
+        assert_eq!(machine.registers.get("a"), Some(3));
+
+        // hit again at each loop
+        assert_eq!(
+            machine.run(|_, _| ()),
+            Stop::Breakpoint(Breakpoint::Instruction(1))
+        );
+        assert_eq!(machine.registers.get("a"), Some(2));
+
+        machine.remove_breakpoint(Breakpoint::Instruction(1));
+        assert_eq!(machine.run(|_, _| ()), Stop::Halted);
+        assert_eq!(machine.registers.to_string(), "a=0 b=7");
+    }
+
+    #[test]
+    fn test_breakpoints_before_start() {
+        let mut machine = set().load("noop\nnoop").unwrap();
+        machine.add_breakpoint(Breakpoint::Cycle(1));
+        assert_eq!(
+            machine.run(|_, _| ()),
+            Stop::Breakpoint(Breakpoint::Cycle(1))
+        );
+        assert_eq!((machine.cycle(), machine.pc()), (0, 0));
+        assert_eq!(machine.run(|_, _| ()), Stop::Halted);
+        assert_eq!(machine.cycle(), 2);
+
+        let mut machine = set().load("noop\nnoop").unwrap();
+        machine.add_breakpoint(Breakpoint::Instruction(0));
+        assert_eq!(
+            machine.run(|_, _| ()),
+            Stop::Breakpoint(Breakpoint::Instruction(0))
+        );
+        assert_eq!((machine.cycle(), machine.pc()), (0, 0));
+        // running again goes past the breakpoint it stopped at
+        assert_eq!(machine.run(|_, _| ()), Stop::Halted);
+        assert_eq!(machine.cycle(), 2);
+    }
+}